@@ -0,0 +1,96 @@
+// Times the pathfinder on large generated maps. Run with `cargo bench`.
+
+#[macro_use]
+extern crate slr_config;
+extern crate rand;
+
+#[allow(dead_code)]
+#[path = "../src/topology.rs"]
+mod topology;
+#[allow(dead_code)]
+#[path = "../src/pathfind.rs"]
+mod pathfind;
+
+use std::time::Instant;
+use rand::{Rng, SeedableRng, StdRng};
+use pathfind::{Grid, Pathfinder};
+use topology::Topology;
+
+const NUM_SEARCHES: usize = 200;
+
+// Snow with scattered lakes, like the hand-made maps but much bigger.
+fn generate_map(size: i32, rng: &mut StdRng) -> Vec<bool>
+{
+	let mut solid = vec![false; (size * size) as usize];
+	for _ in 0..size * size / 64
+	{
+		let (cx, cy) = (rng.gen_range(0, size), rng.gen_range(0, size));
+		let r = rng.gen_range(1, 5);
+		for y in (cy - r).max(0)..(cy + r + 1).min(size)
+		{
+			for x in (cx - r).max(0)..(cx + r + 1).min(size)
+			{
+				if (x - cx) * (x - cx) + (y - cy) * (y - cy) <= r * r
+				{
+					solid[(y * size + x) as usize] = true;
+				}
+			}
+		}
+	}
+	solid
+}
+
+fn bench(name: &str, size: i32, topology: Topology, max_cost: i32)
+{
+	let mut rng: StdRng = SeedableRng::from_seed(&[size as usize, max_cost as usize][..]);
+	let solid = generate_map(size, &mut rng);
+	let is_solid = |x: i32, y: i32| x < 0 || y < 0 || x >= size || y >= size || solid[(y * size + x) as usize];
+	let can_step = |x: i32, y: i32, dx: i32, dy: i32| !is_solid(x + dx, y + dy);
+	let grid = Grid
+	{
+		width: size,
+		height: size,
+		topology,
+		diagonal_cost: 2,
+	};
+	let mut open_tiles = vec![];
+	while open_tiles.len() < 2 * NUM_SEARCHES
+	{
+		let (x, y) = (rng.gen_range(0, size), rng.gen_range(0, size));
+		if !is_solid(x, y)
+		{
+			open_tiles.push((x, y));
+		}
+	}
+
+	let mut pathfinder = Pathfinder::new();
+	let mut path = vec![];
+	let mut num_found = 0;
+	let mut total_len = 0;
+	let start = Instant::now();
+	for pair in open_tiles.chunks(2)
+	{
+		if pathfinder.find_path(&grid, pair[0], pair[1], max_cost, &can_step, &mut path)
+		{
+			num_found += 1;
+		}
+		total_len += path.len();
+	}
+	let elapsed = start.elapsed();
+	println!("{:<28} {:>10.1} us/search ({}/{} found, {:.1} steps on average)", name,
+		elapsed.as_secs() as f64 * 1e6 / NUM_SEARCHES as f64 + elapsed.subsec_nanos() as f64 / 1e3 / NUM_SEARCHES as f64,
+		num_found, NUM_SEARCHES, total_len as f64 / NUM_SEARCHES as f64);
+}
+
+fn main()
+{
+	// Unit sized searches, like the ones made every time a unit is ordered.
+	bench("256x256 square, 8 AP", 256, Topology::Square, 8);
+	bench("256x256 diagonal, 8 AP", 256, Topology::Diagonal, 8);
+	bench("256x256 hex, 8 AP", 256, Topology::Hex, 8);
+	// Searches across the whole map, which is the worst case.
+	bench("256x256 square", 256, Topology::Square, i32::MAX);
+	bench("1024x1024 square", 1024, Topology::Square, i32::MAX);
+	bench("1024x1024 diagonal", 1024, Topology::Diagonal, i32::MAX);
+	bench("1024x1024 hex", 1024, Topology::Hex, i32::MAX);
+}
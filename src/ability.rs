@@ -0,0 +1,222 @@
+use engine::id_map::{IdMap, HasId};
+use game_state::*;
+use map::{xy_to_index, map_tile_solid, line_of_sight, freeze_around};
+use topology::{neighbour_offsets, tile_dist, tile_to_world};
+use effect::create_spawn;
+use combat::Attack;
+use path::resolve_attack;
+use announce::create_announcement;
+
+slr_def!
+{
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	pub enum AbilityKind
+	{
+		// Restores power health to an ally.
+		Heal,
+		// Shoves an enemy power tiles away.
+		Push,
+		// Turns a water tile into snow.
+		Freeze,
+		// Teleports to a free tile.
+		Blink,
+		// Hits every enemy within radius of the target with the unit's usual attack.
+		AreaAttack
+	}
+}
+
+slr_def!
+{
+	#[derive(Clone, Debug)]
+	pub struct AbilityConfig
+	{
+		kind: AbilityKind = AbilityKind::Heal,
+		// Defaults to the kind.
+		name: String = String::new(),
+		// In action points.
+		cost: i32 = 2,
+		// In tiles.
+		range: i32 = 1,
+		// Turns to wait before using it again.
+		cooldown: i32 = 0,
+		power: i32 = 1,
+		radius: i32 = 1
+	}
+}
+
+impl Default for AbilityConfig
+{
+	fn default() -> AbilityConfig
+	{
+		AbilityConfig::new()
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Ability
+{
+	pub config: AbilityConfig,
+	// Turns left until it can be used again.
+	pub cooldown: i32,
+}
+
+impl Ability
+{
+	pub fn new(config: AbilityConfig) -> Ability
+	{
+		Ability
+		{
+			config,
+			cooldown: 0,
+		}
+	}
+
+	pub fn name(&self) -> String
+	{
+		if self.config.name.is_empty()
+		{
+			format!("{:?}", self.config.kind)
+		}
+		else
+		{
+			self.config.name.clone()
+		}
+	}
+}
+
+// Whether obj could use the ability on the target tile while standing on the from tile.
+pub fn can_target(obj: &Object, ability: &Ability, from: (i32, i32), target_x: i32, target_y: i32, objects: &IdMap<Object>, map_data: &MapData) -> bool
+{
+	let (x, y) = from;
+	let dist = tile_dist(map_data.topology, x, y, target_x, target_y);
+	if ability.cooldown > 0 || dist > ability.config.range
+	{
+		return false;
+	}
+	let unit_at = |team: bool| objects.elems().iter().find(|other| other.can_act && other.has_pos && other.get_id() != obj.get_id() &&
+		other.is_ours == team && other.tile_x == target_x && other.tile_y == target_y);
+	match ability.config.kind
+	{
+		AbilityKind::Heal => unit_at(obj.is_ours).is_some_and(|ally| ally.health < ally.max_health),
+		AbilityKind::Push => dist == 1 && unit_at(!obj.is_ours).is_some(),
+		AbilityKind::Freeze => map_data.tiles[xy_to_index(target_x, target_y, map_data.width, map_data.height)] == 'w',
+		AbilityKind::Blink => dist > 0 && !map_tile_solid(target_x, target_y, map_data),
+		AbilityKind::AreaAttack => line_of_sight(x, y, target_x, target_y, map_data),
+	}
+}
+
+// The next tile along the way from one tile to another, continuing past it.
+fn step_away(from: (i32, i32), cur: (i32, i32), map_data: &MapData) -> (i32, i32)
+{
+	let (dir_x, dir_y) = (cur.0 - from.0, cur.1 - from.1);
+	let dist = tile_dist(map_data.topology, from.0, from.1, cur.0, cur.1);
+	neighbour_offsets(map_data.topology, cur.1).iter()
+		.map(|&(dx, dy)| (cur.0 + dx, cur.1 + dy))
+		.filter(|&(x, y)| tile_dist(map_data.topology, from.0, from.1, x, y) > dist)
+		.max_by_key(|&(x, y)| (x - cur.0) * dir_x + (y - cur.1) * dir_y)
+		.unwrap_or(cur)
+}
+
+fn move_unit(obj: &mut Object, x: i32, y: i32, map_data: &MapData)
+{
+	let (world_x, world_y) = tile_to_world(map_data.topology, x, y);
+	obj.x = world_x;
+	obj.y = world_y;
+	obj.tile_x = x;
+	obj.tile_y = y;
+}
+
+// Carries out the ability and pays for it. It may have stopped making sense since it was ordered,
+// in which case nothing happens and nothing is spent.
+pub fn use_ability(user_id: usize, idx: usize, target_x: i32, target_y: i32, objects: &mut IdMap<Object>, map_data: &mut MapData, state: &mut GameState)
+{
+	let (user_x, user_y, is_ours, config) = match objects.get(user_id)
+	{
+		Some(user) if idx < user.abilities.len() =>
+		{
+			let ability = &user.abilities[idx];
+			if ability.config.cost > user.action_points ||
+				!can_target(user, ability, (user.tile_x, user.tile_y), target_x, target_y, objects, map_data)
+			{
+				info!("{} fizzles", ability.name());
+				let announce = create_announcement(state.current_map_id, &format!("{} fizzles!", ability.name()), state);
+				state.add_object(announce);
+				return;
+			}
+			(user.tile_x, user.tile_y, user.is_ours, ability.config.clone())
+		},
+		_ => return,
+	};
+	let user = objects.get_mut(user_id).unwrap();
+	user.action_points -= config.cost;
+	user.abilities[idx].cooldown = config.cooldown;
+	info!("Using {:?} on {} {}", config.kind, target_x, target_y);
+	let target = objects.elems().iter().find(|obj| obj.can_act && obj.has_pos && obj.tile_x == target_x && obj.tile_y == target_y)
+		.map(|obj| obj.get_id());
+	match config.kind
+	{
+		AbilityKind::Heal =>
+		{
+			let ally = objects.get_mut(target.unwrap()).unwrap();
+			ally.health = (ally.health + config.power).min(ally.max_health);
+			let effect = create_spawn(state.current_map_id, ally.x, ally.y, state);
+			state.add_object(effect);
+		},
+		AbilityKind::Push =>
+		{
+			let mut dest = (target_x, target_y);
+			for _ in 0..config.power
+			{
+				let next = step_away((user_x, user_y), dest, map_data);
+				if next == dest || map_tile_solid(next.0, next.1, map_data)
+				{
+					break;
+				}
+				dest = next;
+			}
+			let enemy = objects.get_mut(target.unwrap()).unwrap();
+			move_unit(enemy, dest.0, dest.1, map_data);
+		},
+		AbilityKind::Freeze =>
+		{
+			freeze_around(target_x, target_y, 0, map_data);
+		},
+		AbilityKind::Blink =>
+		{
+			let user = objects.get_mut(user_id).unwrap();
+			let effect = create_spawn(state.current_map_id, user.x, user.y, state);
+			state.add_object(effect);
+			move_unit(user, target_x, target_y, map_data);
+			let effect = create_spawn(state.current_map_id, user.x, user.y, state);
+			state.add_object(effect);
+		},
+		AbilityKind::AreaAttack =>
+		{
+			let mut attacks = vec![];
+			for obj in objects.elems()
+			{
+				if obj.can_act && obj.has_pos && obj.is_ours != is_ours &&
+					tile_dist(map_data.topology, target_x, target_y, obj.tile_x, obj.tile_y) <= config.radius
+				{
+					let mut attack = Attack::new(objects.get(user_id).unwrap(), obj.tile_x, obj.tile_y);
+					// Too much going on to strike back.
+					attack.attacker_id = None;
+					attacks.push(attack);
+				}
+			}
+			for attack in attacks
+			{
+				resolve_attack(attack, objects, map_data, state);
+			}
+		},
+	}
+}
+
+// Called for every unit at the start of its side's phase.
+pub fn tick_cooldowns(obj: &mut Object)
+{
+	for ability in &mut obj.abilities
+	{
+		ability.cooldown = (ability.cooldown - 1).max(0);
+	}
+}
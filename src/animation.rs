@@ -88,6 +88,12 @@ impl Animation
 	}
 
 	pub fn draw(&self, x: f32, y: f32, state: &GameState)
+	{
+		self.draw_flipped(x, y, false, state);
+	}
+
+	// Like draw, but mirrored left to right if flip is set.
+	pub fn draw_flipped(&self, x: f32, y: f32, flip: bool, state: &GameState)
 	{
 		let raw_frame = (self.num_frames as f64 * (state.time - self.offset_time) * self.config.rate as f64) as i32;
 		if self.play_once && raw_frame >= self.num_frames
@@ -97,7 +103,8 @@ impl Animation
 		let frame = raw_frame % self.num_frames;
 		let sx = (frame % self.num_frames_x * self.config.width) as f32;
 		let sy = (frame / self.num_frames_x * self.config.height) as f32;
-		state.core.draw_bitmap_region(&*self.bmp, sx, sy, self.config.width as f32, self.config.height as f32, x + self.config.x_offset as f32, y + self.config.y_offset as f32, Flag::zero());
+		let flags = if flip { FLIP_HORIZONTAL } else { Flag::zero() };
+		state.core.draw_bitmap_region(&*self.bmp, sx, sy, self.config.width as f32, self.config.height as f32, x + self.config.x_offset as f32, y + self.config.y_offset as f32, flags);
 	}
 
 	pub fn get_width(&self) -> i32
@@ -17,6 +17,19 @@ pub fn create_announcement(parent: usize, text: &str, state: &mut GameState) ->
 	obj
 }
 
+// Adds one announcement per line of text, stacked above each other.
+pub fn announce_lines(parent: usize, text: &str, extra_time: f64, state: &mut GameState)
+{
+	let lines: Vec<_> = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+	for (i, line) in lines.iter().enumerate()
+	{
+		let mut announce = create_announcement(parent, line, state);
+		announce.y = -10.0 * (lines.len() - i) as f32;
+		announce.start_fall_time += extra_time;
+		state.add_object(announce);
+	}
+}
+
 simple_behavior!
 {
 	AnnounceDraw[obj.is_announcement] |obj, state|
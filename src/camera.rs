@@ -0,0 +1,225 @@
+use allegro::*;
+use game_state::*;
+
+pub const ZOOM_LEVELS: [f32; 3] = [0.5, 1.0, 2.0];
+const EDGE_SPEED: f32 = 256.0;
+const KEY_MAX_SPEED: f32 = 512.0;
+const KEY_ACCEL: f32 = 1024.0;
+const FOLLOW_RATE: f32 = 6.0;
+
+// Size of the visible area in world coordinates.
+pub fn get_view_size(map_data: &MapData, state: &GameState) -> (f32, f32)
+{
+	(state.buffer.get_width() as f32 / map_data.camera_zoom, state.buffer.get_height() as f32 / map_data.camera_zoom)
+}
+
+pub fn center_camera_on(x: f32, y: f32, map_data: &mut MapData, state: &GameState)
+{
+	let (view_w, view_h) = get_view_size(map_data, state);
+	map_data.camera_x = x - view_w / 2.0;
+	map_data.camera_y = y - view_h / 2.0;
+}
+
+// Converts display coordinates to world coordinates.
+pub fn screen_to_world(mouse_x: i32, mouse_y: i32, map_data: &MapData) -> (f32, f32)
+{
+	(mouse_x as f32 / SCALE / map_data.camera_zoom + map_data.camera_x,
+	 mouse_y as f32 / SCALE / map_data.camera_zoom + map_data.camera_y)
+}
+
+fn clamp_camera(map_data: &mut MapData, state: &GameState)
+{
+	let (view_w, view_h) = get_view_size(map_data, state);
+	let world_w = ((map_data.width - 1) * 32) as f32;
+	let world_h = ((map_data.height - 1) * 32) as f32;
+	// Maps smaller than the view get centred.
+	map_data.camera_x = if view_w >= world_w
+	{
+		(world_w - view_w) / 2.0
+	}
+	else
+	{
+		map_data.camera_x.clamp(0.0, world_w - view_w)
+	};
+	map_data.camera_y = if view_h >= world_h
+	{
+		(world_h - view_h) / 2.0
+	}
+	else
+	{
+		map_data.camera_y.clamp(0.0, world_h - view_h)
+	};
+}
+
+fn set_zoom(zoom_change: i32, map_data: &mut MapData, state: &GameState)
+{
+	let cur_level = ZOOM_LEVELS.iter().position(|&z| z == map_data.camera_zoom).unwrap_or(1) as i32;
+	let new_level = (cur_level + zoom_change).clamp(0, ZOOM_LEVELS.len() as i32 - 1);
+	let (view_w, view_h) = get_view_size(map_data, state);
+	let center_x = map_data.camera_x + view_w / 2.0;
+	let center_y = map_data.camera_y + view_h / 2.0;
+	map_data.camera_zoom = ZOOM_LEVELS[new_level as usize];
+	center_camera_on(center_x, center_y, map_data, state);
+}
+
+fn camera_key_index(k: KeyCode) -> Option<usize>
+{
+	match k
+	{
+		KeyCode::Left | KeyCode::A => Some(0),
+		KeyCode::Right | KeyCode::D => Some(1),
+		KeyCode::Up | KeyCode::W => Some(2),
+		KeyCode::Down | KeyCode::S => Some(3),
+		_ => None,
+	}
+}
+
+pub struct CameraInput;
+
+complex_behavior!
+{
+	CameraInput[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let mut map_data = map_data.borrow_mut();
+
+			if let Some(k) = state.key_down
+			{
+				if let Some(idx) = camera_key_index(k)
+				{
+					map_data.camera_keys[idx] = true;
+				}
+				match k
+				{
+					KeyCode::Space =>
+					{
+						if let Some(obj) = objects.elems().iter().find(|obj| obj.selected && obj.has_pos)
+						{
+							center_camera_on(obj.x, obj.y, &mut map_data, state);
+						}
+					},
+					KeyCode::Equals | KeyCode::PadPlus => set_zoom(1, &mut map_data, state),
+					KeyCode::Minus | KeyCode::PadMinus => set_zoom(-1, &mut map_data, state),
+					_ => ()
+				}
+			}
+			if let Some(k) = state.key_up
+			{
+				if let Some(idx) = camera_key_index(k)
+				{
+					map_data.camera_keys[idx] = false;
+				}
+			}
+			if state.mouse_dz != 0
+			{
+				set_zoom(state.mouse_dz, &mut map_data, state);
+			}
+
+			if state.mouse_button_up == Some(3)
+			{
+				map_data.camera_drag = None;
+			}
+
+			map_data.camera_edge_x = 0.0;
+			map_data.camera_edge_y = 0.0;
+			if let (Some(mouse_x), Some(mouse_y)) = (state.mouse_x, state.mouse_y)
+			{
+				if state.mouse_button_down == Some(3)
+				{
+					map_data.camera_drag = Some((mouse_x, mouse_y, map_data.camera_x, map_data.camera_y));
+				}
+				if let Some((start_x, start_y, start_camera_x, start_camera_y)) = map_data.camera_drag
+				{
+					let scale = SCALE * map_data.camera_zoom;
+					map_data.camera_x = start_camera_x - (mouse_x - start_x) as f32 / scale;
+					map_data.camera_y = start_camera_y - (mouse_y - start_y) as f32 / scale;
+					return;
+				}
+
+				if mouse_x < 8 * SCALE as i32
+				{
+					map_data.camera_edge_x = -1.0;
+				}
+				if mouse_x > state.disp.get_width() - 8 * SCALE as i32
+				{
+					map_data.camera_edge_x = 1.0;
+				}
+				if mouse_y < 8 * SCALE as i32
+				{
+					map_data.camera_edge_y = -1.0;
+				}
+				if mouse_y > state.disp.get_height() - 8 * SCALE as i32
+				{
+					map_data.camera_edge_y = 1.0;
+				}
+			}
+		}
+	}
+}
+
+simple_behavior!
+{
+	CameraDraw[obj.is_map] |obj, state|
+	{
+		let map_data = obj.map_data.borrow();
+		let zoom = map_data.camera_zoom;
+		let mut trans = Transform::identity();
+		trans.translate(-(map_data.camera_x * zoom).floor() / zoom, -(map_data.camera_y * zoom).floor() / zoom);
+		trans.scale(zoom, zoom);
+		state.core.use_transform(&trans);
+		break;
+	}
+}
+
+pub struct CameraLogic;
+
+complex_behavior!
+{
+	CameraLogic[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let mut map_data = map_data.borrow_mut();
+			let zoom = map_data.camera_zoom;
+
+			let key_x = (map_data.camera_keys[1] as i32 - map_data.camera_keys[0] as i32) as f32;
+			let key_y = (map_data.camera_keys[3] as i32 - map_data.camera_keys[2] as i32) as f32;
+			if key_x != 0.0 || key_y != 0.0
+			{
+				map_data.camera_vx = (map_data.camera_vx + key_x * KEY_ACCEL * DT).clamp(-KEY_MAX_SPEED, KEY_MAX_SPEED);
+				map_data.camera_vy = (map_data.camera_vy + key_y * KEY_ACCEL * DT).clamp(-KEY_MAX_SPEED, KEY_MAX_SPEED);
+				if key_x == 0.0
+				{
+					map_data.camera_vx = 0.0;
+				}
+				if key_y == 0.0
+				{
+					map_data.camera_vy = 0.0;
+				}
+			}
+			else
+			{
+				map_data.camera_vx = map_data.camera_edge_x * EDGE_SPEED;
+				map_data.camera_vy = map_data.camera_edge_y * EDGE_SPEED;
+			}
+			map_data.camera_x += DT * map_data.camera_vx / zoom;
+			map_data.camera_y += DT * map_data.camera_vy / zoom;
+
+			if map_data.camera_drag.is_none() && map_data.camera_vx == 0.0 && map_data.camera_vy == 0.0
+			{
+				if let Some(obj) = objects.elems().iter().find(|obj| obj.executing_orders && obj.has_pos)
+				{
+					let (view_w, view_h) = get_view_size(&map_data, state);
+					let f = (FOLLOW_RATE * DT).min(1.0);
+					map_data.camera_x += (obj.x - view_w / 2.0 - map_data.camera_x) * f;
+					map_data.camera_y += (obj.y - view_h / 2.0 - map_data.camera_y) * f;
+				}
+			}
+
+			clamp_camera(&mut map_data, state);
+		}
+	}
+}
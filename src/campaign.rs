@@ -0,0 +1,185 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use slr_config::ElementRepr;
+use util::populate_from_file;
+use game_state::*;
+use map::create_map;
+use announce::announce_lines;
+use main_menu::create_level_select;
+use engine::id_map::IdMap;
+use experience::{Veteran, MAX_ROSTER, make_veteran, surviving_allies};
+
+const PROGRESS_FILE: &str = "progress.cfg";
+
+slr_def!
+{
+	#[derive(Clone, Debug, Default)]
+    pub struct CampaignMapConfig
+    {
+        file: String = String::new(),
+        title: String = String::new(),
+        intro: String = String::new(),
+        unlock: Vec<String> = vec![],
+        // Whether the allies from earlier maps come along. If not, they wait for the next one.
+        roster: i32 = 1
+    }
+}
+
+slr_def!
+{
+	#[derive(Clone, Debug)]
+    pub struct CampaignConfig
+    {
+        maps: Vec<CampaignMapConfig> = vec![]
+    }
+}
+
+slr_def!
+{
+	#[derive(Clone, Debug)]
+    pub struct ProgressConfig
+    {
+        cleared: Vec<String> = vec![],
+        wizard: Veteran = Veteran::new(),
+        roster: Vec<Veteran> = vec![],
+        // The seed each map was last cleared with.
+        seeds: Vec<MapSeed> = vec![]
+    }
+}
+
+slr_def!
+{
+	#[derive(Clone, Debug)]
+    pub struct MapSeed
+    {
+        file: String = String::new(),
+        seed: i32 = 0
+    }
+}
+
+impl Default for MapSeed
+{
+	fn default() -> MapSeed
+	{
+		MapSeed::new()
+	}
+}
+
+pub fn load_campaign() -> Vec<CampaignMapConfig>
+{
+	let mut config = CampaignConfig::new();
+	populate_from_file("data/campaign.cfg", &mut config).unwrap();
+	config.maps
+}
+
+pub fn load_progress() -> ProgressConfig
+{
+	let mut progress = ProgressConfig::new();
+	if Path::new(PROGRESS_FILE).exists() && populate_from_file(PROGRESS_FILE, &mut progress).is_err()
+	{
+		warn!("Could not read {}, starting with no progress.", PROGRESS_FILE);
+		progress = ProgressConfig::new();
+	}
+	progress
+}
+
+pub fn save_progress(progress: &ProgressConfig)
+{
+	let res = File::create(PROGRESS_FILE).and_then(|mut f| f.write_all(progress.to_element().to_string().as_bytes()));
+	if let Err(e) = res
+	{
+		warn!("Could not save {}: {}", PROGRESS_FILE, e);
+	}
+}
+
+pub fn is_cleared(idx: usize, state: &GameState) -> bool
+{
+	state.progress.cleared.contains(&state.campaign[idx].file)
+}
+
+pub fn is_unlocked(idx: usize, state: &GameState) -> bool
+{
+	state.campaign[idx].unlock.iter().all(|file| state.progress.cleared.contains(file))
+}
+
+// Also remembers the seed, for playing the map again the same way.
+pub fn mark_cleared(idx: usize, seed: u32, state: &mut GameState)
+{
+	let file = state.campaign[idx].file.clone();
+	if !is_cleared(idx, state)
+	{
+		state.progress.cleared.push(file.clone());
+	}
+	// Stored the way a map's own seed is written.
+	let seed = seed as i32;
+	match state.progress.seeds.iter_mut().find(|map_seed| map_seed.file == file)
+	{
+		Some(map_seed) => map_seed.seed = seed,
+		None => state.progress.seeds.push(MapSeed { file, seed }),
+	}
+	save_progress(&state.progress);
+}
+
+// 0 if the map hasn't been cleared yet.
+fn cleared_seed(idx: usize, state: &GameState) -> u32
+{
+	state.progress.seeds.iter().find(|map_seed| map_seed.file == state.campaign[idx].file).map_or(0, |map_seed| map_seed.seed as u32)
+}
+
+pub fn is_last_map(idx: usize, state: &GameState) -> bool
+{
+	idx + 1 >= state.campaign.len()
+}
+
+pub fn start_campaign_map(idx: usize, state: &mut GameState) -> Object
+{
+	let map_config = state.campaign[idx].clone();
+	let obj = create_map(&map_config.file, cleared_seed(idx, state), state);
+	obj.map_data.borrow_mut().campaign_idx = idx;
+	obj.map_data.borrow_mut().pending_wizard = Some(state.progress.wizard.clone());
+	if map_config.roster != 0
+	{
+		obj.map_data.borrow_mut().pending_roster = state.progress.roster.clone();
+	}
+	announce_lines(state.current_map_id, &map_config.intro, 2.0, state);
+	obj
+}
+
+// Remembers how the wizard and the surviving summoned allies did, for the next map.
+pub fn save_veterans(objects: &IdMap<Object>, map_data: &MapData, state: &mut GameState)
+{
+	if let Some(wizard) = objects.get(map_data.wizard_id)
+	{
+		state.progress.wizard = make_veteran(wizard);
+	}
+	let mut roster = surviving_allies(objects.elems());
+	if state.campaign[map_data.campaign_idx].roster == 0
+	{
+		roster.extend(state.progress.roster.iter().cloned());
+		roster.truncate(MAX_ROSTER);
+	}
+	info!("{} allies carry over", roster.len());
+	state.progress.roster = roster;
+	save_progress(&state.progress);
+}
+
+// Returns the object to switch to once the map at idx is won.
+pub fn advance_campaign(idx: usize, state: &mut GameState) -> Object
+{
+	if is_last_map(idx, state)
+	{
+		info!("Campaign complete, going back to level select!");
+		create_level_select(state)
+	}
+	// The next map may want more than this one cleared.
+	else if !is_unlocked(idx + 1, state)
+	{
+		info!("{} is still locked, going back to level select!", state.campaign[idx + 1].file);
+		create_level_select(state)
+	}
+	else
+	{
+		start_campaign_map(idx + 1, state)
+	}
+}
@@ -0,0 +1,177 @@
+use std::cmp::max;
+use rand::Rng;
+use engine::id_map::HasId;
+use game_state::Object;
+use status::{StatusKind, has_status};
+
+slr_def!
+{
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	pub enum DamageType
+	{
+		Physical,
+		// Melts snow where it lands.
+		Fire,
+		Frost,
+		Arcane
+	}
+}
+
+slr_def!
+{
+	// In percent, negative for a weakness. 100 makes a unit immune.
+	#[derive(Clone, Debug)]
+	pub struct Resistances
+	{
+		physical: i32 = 0,
+		fire: i32 = 0,
+		frost: i32 = 0,
+		arcane: i32 = 0
+	}
+}
+
+impl Resistances
+{
+	pub fn get(&self, damage_type: DamageType) -> i32
+	{
+		match damage_type
+		{
+			DamageType::Physical => self.physical,
+			DamageType::Fire => self.fire,
+			DamageType::Frost => self.frost,
+			DamageType::Arcane => self.arcane,
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Attack
+{
+	pub x: i32,
+	pub y: i32,
+	pub damage: i32,
+	pub damage_type: DamageType,
+	// In percent.
+	pub accuracy: i32,
+	pub crit_chance: i32,
+	pub damage_spread: i32,
+	// Who to strike back at, if anyone.
+	pub attacker_id: Option<usize>,
+	// Who gets the experience for a kill.
+	pub source_id: usize,
+}
+
+impl Attack
+{
+	pub fn new(attacker: &Object, x: i32, y: i32) -> Attack
+	{
+		let damage = if has_status(attacker, StatusKind::Enchanted) { attacker.damage * 3 / 2 } else { attacker.damage };
+		Attack
+		{
+			x,
+			y,
+			damage,
+			damage_type: attacker.damage_type,
+			accuracy: attacker.accuracy,
+			crit_chance: attacker.crit_chance,
+			damage_spread: attacker.damage_spread,
+			attacker_id: if attacker.no_retaliation { None } else { Some(attacker.get_id()) },
+			source_id: attacker.get_id(),
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Roll
+{
+	Miss,
+	Hit(i32),
+	Critical(i32),
+}
+
+// Whether the attack lands and for how much, before the target's defences. Deterministic maps have
+// every attack hit for its listed damage.
+pub fn roll_attack<R: Rng>(attack: &Attack, evasion: i32, deterministic: bool, rng: &mut R) -> Roll
+{
+	if deterministic
+	{
+		return Roll::Hit(attack.damage);
+	}
+	// Every attack takes the same three numbers, whatever it rolls, so a replay can't drift.
+	let hit_roll = rng.gen_range(0, 100);
+	let crit_roll = rng.gen_range(0, 100);
+	let damage = max(attack.damage + rng.gen_range(-attack.damage_spread, attack.damage_spread + 1), 0);
+	if hit_roll >= attack.accuracy - evasion
+	{
+		Roll::Miss
+	}
+	else if crit_roll < attack.crit_chance
+	{
+		Roll::Critical(damage * 2)
+	}
+	else
+	{
+		Roll::Hit(damage)
+	}
+}
+
+// How much health a hit takes off. Armour only stops physical damage, and never all of it.
+pub fn damage_taken(damage: i32, damage_type: DamageType, armour: i32, resistances: &Resistances) -> i32
+{
+	let damage = if damage_type == DamageType::Physical && damage > 0
+	{
+		max(damage - armour, 1)
+	}
+	else
+	{
+		damage
+	};
+	// Rounded to the nearest point.
+	max((damage * (100 - resistances.get(damage_type)) + 50) / 100, 0)
+}
+
+#[test]
+fn seeded_rolls()
+{
+	use rand::{SeedableRng, XorShiftRng};
+
+	let attack = Attack
+	{
+		x: 0,
+		y: 0,
+		damage: 3,
+		damage_type: DamageType::Physical,
+		accuracy: 80,
+		crit_chance: 10,
+		damage_spread: 1,
+		attacker_id: None,
+		source_id: 0,
+	};
+	let mut rng1 = XorShiftRng::from_seed([1, 2, 3, 4]);
+	let mut rng2 = XorShiftRng::from_seed([1, 2, 3, 4]);
+	let rolls: Vec<_> = (0..100).map(|_| roll_attack(&attack, 10, false, &mut rng1)).collect();
+	assert!(rolls.iter().all(|&r| r == roll_attack(&attack, 10, false, &mut rng2)));
+	assert!(rolls.contains(&Roll::Miss));
+	assert!(rolls.iter().all(|&r| match r
+	{
+		Roll::Miss => true,
+		Roll::Hit(damage) => (2..=4).contains(&damage),
+		Roll::Critical(damage) => (4..=8).contains(&damage),
+	}));
+	assert_eq!(Roll::Hit(3), roll_attack(&attack, 100, true, &mut rng1));
+}
+
+#[test]
+fn defences()
+{
+	let mut resistances = Resistances::new();
+	assert_eq!(3, damage_taken(3, DamageType::Frost, 0, &resistances));
+	assert_eq!(1, damage_taken(3, DamageType::Physical, 5, &resistances));
+	assert_eq!(3, damage_taken(3, DamageType::Fire, 5, &resistances));
+	resistances.frost = -100;
+	assert_eq!(6, damage_taken(3, DamageType::Frost, 0, &resistances));
+	resistances.frost = 50;
+	assert_eq!(2, damage_taken(3, DamageType::Frost, 0, &resistances));
+	resistances.frost = 100;
+	assert_eq!(0, damage_taken(3, DamageType::Frost, 0, &resistances));
+}
@@ -2,6 +2,9 @@
 
 use std::collections::HashMap;
 use util::populate_from_file;
+use status::Status;
+
+pub const CURSE_RADIUS: i32 = 2;
 
 slr_def!
 {
@@ -9,7 +12,14 @@ slr_def!
     pub struct SpellConfig
     {
         poem: String = "".to_string(),
-        summon: String = "".to_string()
+        name: String = "".to_string(),
+        summon: String = "".to_string(),
+        // Radius of water around the wizard to freeze into snow.
+        freeze: i32 = 0,
+        // Given to the wizard and the allies next to it.
+        bless: Vec<Status> = vec![],
+        // Given to the enemies within CURSE_RADIUS of the wizard.
+        curse: Vec<Status> = vec![]
     }
 }
 
@@ -22,7 +32,7 @@ slr_def!
     }
 }
 
-pub fn load_spells() -> HashMap<String, String>
+pub fn load_spells() -> HashMap<String, SpellConfig>
 {
 	let mut config = SpellsConfig::new();
 	populate_from_file("data/spells.cfg", &mut config).unwrap();
@@ -42,7 +52,7 @@ pub fn load_spells() -> HashMap<String, String>
 			cleaned.push_str("\n");
 		}
 		cleaned.pop();
-		ret.insert(cleaned, spell.summon.clone());
+		ret.insert(cleaned, spell);
 	}
 	ret
 }
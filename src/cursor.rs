@@ -1,5 +1,7 @@
 use game_state::*;
 use std::cmp::{min, max};
+use camera::screen_to_world;
+use topology::{tile_to_world, world_to_tile};
 
 pub fn create_cursor(parent: usize, state: &mut GameState) -> Object
 {
@@ -25,8 +27,10 @@ complex_behavior!
 				{
 					if self.check_object(obj)
 					{
-						state.cursor_x = (mouse_x / SCALE as i32 + map_data.camera_x as i32 + 16) / 32;
-						state.cursor_y = (mouse_y / SCALE as i32 + map_data.camera_y as i32 + 16) / 32;
+						let (world_x, world_y) = screen_to_world(mouse_x, mouse_y, &map_data);
+						let (cursor_x, cursor_y) = world_to_tile(map_data.topology, world_x, world_y);
+						state.cursor_x = cursor_x;
+						state.cursor_y = cursor_y;
 						
 						state.cursor_x = max(state.cursor_x, 1);
 						state.cursor_y = max(state.cursor_y, 1);
@@ -41,12 +45,17 @@ complex_behavior!
 	}
 }
 
-simple_behavior!
+pub struct CursorDraw;
+
+complex_behavior!
 {
-	CursorDraw[obj.is_cursor] |obj, state|
+	CursorDraw[obj.is_cursor] |self, obj, objects, state|
 	{
-		state.cursor_select.as_ref().unwrap().draw(state.cursor_x as f32 * 32.0 - 16.0, state.cursor_y as f32 * 32.0 - 16.0, state);
-		break;
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let (x, y) = tile_to_world(map_data.borrow().topology, state.cursor_x, state.cursor_y);
+			state.cursor_select.as_ref().unwrap().draw(x - 16.0, y - 16.0, state);
+		}
 	}
 }
-
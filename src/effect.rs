@@ -1,6 +1,11 @@
+use allegro::*;
 use engine::id_map::HasId;
-use game_state::{GameState, Object};
+use game_state::{GameState, Object, DT};
 use animation::Animation;
+use path::resolve_attack;
+use unit::draw_pos;
+use combat::{DamageType, Attack};
+use topology::tile_to_world;
 
 pub fn create_slash(parent: usize, x: f32, y: f32, state: &mut GameState) -> Object
 {
@@ -76,3 +81,80 @@ simple_behavior!
 		}
 	}
 }
+
+// Flies from the attacker to the target tile, and only then does the damage.
+pub fn create_projectile(parent: usize, attacker: &Object, target_x: i32, target_y: i32, state: &mut GameState) -> Object
+{
+	let attack = Attack::new(attacker, target_x, target_y);
+	let mut obj = Object::new(state.new_id());
+	obj.parent = parent;
+	obj.x = attacker.x;
+	obj.y = attacker.y;
+	obj.has_pos = true;
+	obj.is_projectile = true;
+	obj.attack = Some(attack);
+	if attack.damage_type == DamageType::Fire
+	{
+		obj.has_sprite = true;
+		obj.sprite = Some(Animation::new("data/fire.cfg", false, state));
+	}
+	obj
+}
+
+pub struct ProjectileLogic;
+
+complex_behavior!
+{
+	ProjectileLogic[obj.is_projectile && obj.has_pos] |self, obj, objects, state|
+	{
+		const SPEED: f32 = 384.0;
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let mut map_data = map_data.borrow_mut();
+			let mut hits = vec![];
+			for obj in objects.elems_mut()
+			{
+				if !self.check_object(obj)
+				{
+					continue;
+				}
+				let attack = obj.attack.unwrap();
+				let (target_x, target_y) = tile_to_world(map_data.topology, attack.x, attack.y);
+				let dx = target_x - obj.x;
+				let dy = target_y - obj.y;
+				let dist = (dx * dx + dy * dy).sqrt();
+				if dist < SPEED * DT
+				{
+					hits.push(attack);
+					state.remove_object(obj.get_id());
+				}
+				else
+				{
+					obj.x += dx / dist * SPEED * DT;
+					obj.y += dy / dist * SPEED * DT;
+				}
+			}
+			for attack in hits
+			{
+				resolve_attack(attack, objects, &mut map_data, state);
+			}
+		}
+	}
+}
+
+simple_behavior!
+{
+	ProjectileDraw[obj.is_projectile && obj.has_pos] |obj, state|
+	{
+		let (x, y) = draw_pos(obj, state);
+		if let Some(ref sprite) = obj.sprite
+		{
+			sprite.draw(x - 16.0, y - 16.0, state);
+		}
+		else
+		{
+			state.prim.draw_filled_circle(x, y, 3.0, Color::from_rgb(224, 224, 224));
+		}
+	}
+}
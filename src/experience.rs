@@ -0,0 +1,184 @@
+// Experience, levelling and the veterans that carry over between campaign maps.
+
+use std::mem;
+use engine::id_map::HasId;
+use game_state::*;
+use map::map_tile_solid;
+use topology::{tile_dist, tile_to_world};
+use unit::create_unit;
+use effect::create_spawn;
+use announce::create_announcement;
+
+pub const KILL_EXPERIENCE: i32 = 5;
+pub const TURN_EXPERIENCE: i32 = 1;
+// The most allies that follow the wizard from one map to the next.
+pub const MAX_ROSTER: usize = 4;
+// How far from the wizard the roster gets placed, in tiles.
+const ROSTER_RADIUS: i32 = 3;
+
+slr_def!
+{
+	// What a unit gains on reaching a level.
+	#[derive(Clone, Debug)]
+	pub struct Growth
+	{
+		health: i32 = 0,
+		damage: i32 = 0,
+		action_points: i32 = 0
+	}
+}
+
+impl Default for Growth
+{
+	fn default() -> Growth
+	{
+		Growth::new()
+	}
+}
+
+slr_def!
+{
+	// A unit that made it through a map, to be recreated on the next one.
+	#[derive(Clone, Debug)]
+	pub struct Veteran
+	{
+		unit: String = String::new(),
+		level: i32 = 1,
+		experience: i32 = 0
+	}
+}
+
+impl Default for Veteran
+{
+	fn default() -> Veteran
+	{
+		Veteran::new()
+	}
+}
+
+// The growth table has an entry for every level after the first, and the unit stops there.
+fn can_level_up(obj: &Object) -> bool
+{
+	(obj.level as usize) <= obj.growth.len()
+}
+
+pub fn experience_needed(obj: &Object) -> i32
+{
+	obj.experience_per_level * obj.level
+}
+
+fn level_up(obj: &mut Object)
+{
+	let growth = obj.growth[obj.level as usize - 1].clone();
+	obj.max_health += growth.health;
+	obj.health += growth.health;
+	obj.damage += growth.damage;
+	obj.max_action_points += growth.action_points;
+	obj.action_points += growth.action_points;
+	obj.level += 1;
+}
+
+// Only the player's units learn from experience.
+pub fn gain_experience(obj: &mut Object, amount: i32, state: &mut GameState)
+{
+	if !obj.is_ours || !can_level_up(obj)
+	{
+		return;
+	}
+	obj.experience += amount;
+	while can_level_up(obj) && obj.experience >= experience_needed(obj)
+	{
+		obj.experience -= experience_needed(obj);
+		level_up(obj);
+		info!("{} reached level {}", obj.name, obj.level);
+		let announce = create_announcement(state.current_map_id, &format!("{} reached level {}!", obj.name, obj.level), state);
+		state.add_object(announce);
+	}
+}
+
+pub fn make_veteran(obj: &Object) -> Veteran
+{
+	Veteran
+	{
+		unit: obj.unit_file.clone(),
+		level: obj.level,
+		experience: obj.experience,
+	}
+}
+
+// Brings a freshly created unit up to the veteran's level.
+pub fn apply_veteran(obj: &mut Object, veteran: &Veteran)
+{
+	while obj.level < veteran.level && can_level_up(obj)
+	{
+		level_up(obj);
+	}
+	obj.experience = veteran.experience;
+}
+
+pub struct VeteranLogic;
+
+complex_behavior!
+{
+	VeteranLogic[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let mut map_data = map_data.borrow_mut();
+			let (wizard_x, wizard_y) = match objects.get_mut(map_data.wizard_id)
+			{
+				Some(wizard) =>
+				{
+					if let Some(veteran) = map_data.pending_wizard.take()
+					{
+						apply_veteran(wizard, &veteran);
+					}
+					(wizard.tile_x, wizard.tile_y)
+				},
+				None => return,
+			};
+			if map_data.pending_roster.is_empty()
+			{
+				return;
+			}
+			// The closest free tiles around the wizard, nearest first.
+			let mut free = vec![];
+			for dist in 1..ROSTER_RADIUS + 1
+			{
+				for y in wizard_y - dist..wizard_y + dist + 1
+				{
+					for x in wizard_x - dist..wizard_x + dist + 1
+					{
+						if tile_dist(map_data.topology, wizard_x, wizard_y, x, y) == dist && !map_tile_solid(x, y, &map_data)
+						{
+							free.push((x, y));
+						}
+					}
+				}
+			}
+			let roster = mem::take(&mut map_data.pending_roster);
+			for (veteran, (x, y)) in roster.iter().zip(free)
+			{
+				let mut obj = create_unit(state.current_map_id, &veteran.unit, true, state);
+				apply_veteran(&mut obj, veteran);
+				obj.summoned = true;
+				let (world_x, world_y) = tile_to_world(map_data.topology, x, y);
+				obj.x = world_x;
+				obj.y = world_y;
+				info!("{} joins at level {}", obj.name, obj.level);
+				let effect = create_spawn(state.current_map_id, obj.x, obj.y, state);
+				state.add_object(obj);
+				state.add_object(effect);
+			}
+		}
+	}
+}
+
+// The summoned allies that survived the map, the most experienced first.
+pub fn surviving_allies(objects: &[Object]) -> Vec<Veteran>
+{
+	let mut allies: Vec<_> = objects.iter().filter(|obj| obj.is_ours && obj.summoned && obj.can_act && obj.health > 0).collect();
+	allies.sort_by_key(|obj| (-obj.level, -obj.experience, obj.get_id()));
+	allies.into_iter().take(MAX_ROSTER).map(make_veteran).collect()
+}
@@ -5,12 +5,24 @@ use animation::Animation;
 use std::rc::Rc;
 use std::cell::RefCell;
 use std::collections::HashMap;
-use craft::load_spells;
+use craft::{load_spells, SpellConfig};
+use objective::Objective;
+use trigger::{Trigger, GameEvent};
+use topology::Topology;
+use path::{PathPreview, ZoneOfControl};
+use pathfind::Pathfinder;
+use undo::UndoState;
+use combat::{DamageType, Resistances, Attack};
+use status::Status;
+use ability::Ability;
+use experience::{Growth, Veteran};
+use campaign::{load_campaign, load_progress, CampaignMapConfig, ProgressConfig};
 
 use allegro::*;
 use allegro_primitives::*;
 use allegro_font::*;
 use std::collections::HashSet;
+use rand::{SeedableRng, XorShiftRng};
 
 pub const DT: f32 = 1.0 / 120.0;
 pub const SCALE: f32 = 4.0;
@@ -91,7 +103,9 @@ macro_rules! object
 pub struct MapData
 {
 	pub tilesheet: Vec<SubBitmap>,
-	pub collision_map: Vec<bool>,
+	pub terrain_chunks: Vec<Option<Bitmap>>,
+	// The team of the unit standing on each tile, true for ours.
+	pub collision_map: Vec<Option<bool>>,
 	pub tiles: Vec<char>,
 	pub width: i32,
 	pub height: i32,
@@ -100,6 +114,11 @@ pub struct MapData
 	pub camera_y: f32,
 	pub camera_vx: f32,
 	pub camera_vy: f32,
+	pub camera_zoom: f32,
+	pub camera_edge_x: f32,
+	pub camera_edge_y: f32,
+	pub camera_keys: [bool; 4],
+	pub camera_drag: Option<(i32, i32, f32, f32)>,
 	pub our_turn: bool,
 	pub crafting: bool,
 	pub base_inventory: Vec<String>,
@@ -113,7 +132,32 @@ pub struct MapData
 	pub want_next_map: bool,
 	pub change_time: f64,
 	pub filename: String,
-	pub next_map: String,
+	pub campaign_idx: usize,
+	pub objectives: Vec<Objective>,
+	pub triggers: Vec<Trigger>,
+	pub scripted_end: Option<bool>,
+	pub topology: Topology,
+	pub diagonal_cost: i32,
+	pub zone_of_control: ZoneOfControl,
+	pub path_preview: Option<PathPreview>,
+	// Units waiting for their turn to run their orders, when Go runs them one at a time.
+	pub order_queue: Vec<usize>,
+	// How many of our units have orders planned but not started.
+	pub num_planned: usize,
+	// What the player's actions this turn can be undone to, latest last.
+	pub undo_stack: Vec<UndoState>,
+	// Attacks always hit for their listed damage, for puzzle levels.
+	pub deterministic: bool,
+	// What the random numbers were seeded with, so the map can be played the same way again.
+	pub seed: u32,
+	// What the wizard and its allies earned on earlier maps, applied once the map is up.
+	pub pending_wizard: Option<Veteran>,
+	pub pending_roster: Vec<Veteran>,
+	// Bumped whenever the terrain or the tiles the units stand on change, so anything worked out
+	// from them knows to be redone.
+	pub generation: u32,
+	// Scratch space for the path searches.
+	pub pathfinder: RefCell<Pathfinder>,
 }
 
 impl MapData
@@ -123,6 +167,7 @@ impl MapData
 		MapData
 		{
 			tilesheet: vec![],
+			terrain_chunks: vec![],
 			collision_map: vec![],
 			tiles: vec![],
 			width: 0,
@@ -132,6 +177,11 @@ impl MapData
 			camera_y: 0.0,
 			camera_vx: 0.0,
 			camera_vy: 0.0,
+			camera_zoom: 1.0,
+			camera_edge_x: 0.0,
+			camera_edge_y: 0.0,
+			camera_keys: [false; 4],
+			camera_drag: None,
 			our_turn: true,
 			crafting: false,
 			base_inventory: vec!["bösh".to_string(), "bïshi".to_string(), "caråzo".to_string()],
@@ -145,7 +195,23 @@ impl MapData
 			want_next_map: false,
 			change_time: 0.0,
 			filename: "".to_string(),
-			next_map: "".to_string(),
+			campaign_idx: 0,
+			objectives: vec![],
+			triggers: vec![],
+			scripted_end: None,
+			topology: Topology::Square,
+			diagonal_cost: 2,
+			zone_of_control: ZoneOfControl::Off,
+			path_preview: None,
+			order_queue: vec![],
+			num_planned: 0,
+			undo_stack: vec![],
+			deterministic: false,
+			seed: 0,
+			pending_wizard: None,
+			pending_roster: vec![],
+			generation: 0,
+			pathfinder: RefCell::new(Pathfinder::new()),
 		}
 	}
 }
@@ -155,6 +221,17 @@ pub enum OrderType
 {
 	MoveTo,
 	Attack,
+	RangedAttack,
+	// Uses the ability with this index on the tile.
+	Ability(usize),
+}
+
+impl OrderType
+{
+	pub fn is_attack(&self) -> bool
+	{
+		matches!(*self, OrderType::Attack | OrderType::RangedAttack)
+	}
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -163,6 +240,8 @@ pub struct Order
 	pub x: i32,
 	pub y: i32,
 	pub order_type: OrderType,
+	// Action points spent on this step.
+	pub cost: i32,
 }
 
 object!
@@ -176,8 +255,11 @@ object!
 		y: f32 = 0.0,
 		tile_x: i32 = 0,
 		tile_y: i32 = 0,
+		// Where the object was at the start of the last logic tick.
 		old_x: f32 = 0.0,
 		old_y: f32 = 0.0,
+		// The sprites face left.
+		facing_right: bool = false,
 
 		has_vel: bool = false,
 		vx: f32 = 0.0,
@@ -207,14 +289,47 @@ object!
 		action_points: i32 = 0,
 		orders: Vec<Order> = vec![],
 		executing_orders: bool = false,
+		// How far along the step to the first order the unit is, from 0 to 1.
+		step_progress: f32 = 0.0,
+		step_from_x: f32 = 0.0,
+		step_from_y: f32 = 0.0,
 		sight_range: i32 = 0,
 		damage: i32 = 0,
-		fire: bool = false,
+		damage_type: DamageType = DamageType::Physical,
+		armour: i32 = 0,
+		resistances: Resistances = Resistances::new(),
+		// Chances in percent.
+		accuracy: i32 = 100,
+		evasion: i32 = 0,
+		crit_chance: i32 = 0,
+		damage_spread: i32 = 0,
+		// Percent of the damage dealt back when attacked.
+		retaliation: i32 = 0,
+		no_retaliation: bool = false,
+		statuses: Vec<Status> = vec![],
+		abilities: Vec<Ability> = vec![],
+		// The ability the next order uses, picked from the action bar.
+		active_ability: Option<usize> = None,
+		// The cfg the unit was created from.
+		unit_file: String = "".to_string(),
+		level: i32 = 1,
+		// Towards the next level.
+		experience: i32 = 0,
+		experience_per_level: i32 = 0,
+		growth: Vec<Growth> = vec![],
+		// Summoned allies can follow the wizard to the next map.
+		summoned: bool = false,
+		min_range: i32 = 1,
+		max_range: i32 = 1,
+		ignores_zone_of_control: bool = false,
 		
 		is_cursor: bool = false,
 		
 		is_effect: bool = false,
 		effect_death_time: f64 = 0.0,
+
+		is_projectile: bool = false,
+		attack: Option<Attack> = None,
 		
 		is_announcement: bool = false,
 		announcement: String = "".to_string(),
@@ -227,6 +342,7 @@ object!
 		word: String = "".to_string(),
 		
 		is_main_menu: bool = false,
+		is_level_select: bool = false,
 	}
 }
 
@@ -253,7 +369,10 @@ pub struct GameState
 
 	pub key_down: Option<KeyCode>,
 	pub key_up: Option<KeyCode>,
+	pub shift_held: bool,
 	pub mouse_button_down: Option<u32>,
+	pub mouse_button_up: Option<u32>,
+	pub mouse_dz: i32,
 	pub quit: bool,
 	pub paused: bool,
 	pub time: f64,
@@ -275,7 +394,12 @@ pub struct GameState
 	pub blank_button: Option<Animation>,
 	pub newline_button: Option<Animation>,
 	pub backspace_button: Option<Animation>,
-	pub spells: HashMap<String, String>,
+	pub spells: HashMap<String, SpellConfig>,
+	pub campaign: Vec<CampaignMapConfig>,
+	pub progress: ProgressConfig,
+	pub events: Vec<GameEvent>,
+	// Everything random in a level comes from here, so a seed always plays out the same way.
+	pub rng: XorShiftRng,
 	
 	pub mouse_x: Option<i32>,
 	pub mouse_y: Option<i32>,
@@ -310,7 +434,10 @@ impl GameState
 			ui_font: ui_font,
 			key_down: None,
 			key_up: None,
+			shift_held: false,
 			mouse_button_down: None,
+			mouse_button_up: None,
+			mouse_dz: 0,
 			quit: false,
 			paused: false,
 			time: 0.0,
@@ -340,6 +467,10 @@ impl GameState
 			newline_button: None,
 			menu_background: None,
 			spells: load_spells(),
+			campaign: load_campaign(),
+			progress: load_progress(),
+			events: vec![],
+			rng: XorShiftRng::new_unseeded(),
 		};
 		state.cursor_select = Some(Animation::new("data/cursor_select.png", false, &mut state));
 		state.bob_selected = Some(Animation::new("data/bob_selected.cfg", false, &mut state));
@@ -359,6 +490,12 @@ impl GameState
 		state
 	}
 
+	pub fn seed_rng(&mut self, seed: u32)
+	{
+		// XorShift can't take an all zero seed, so pad it with the unseeded state.
+		self.rng = XorShiftRng::from_seed([seed, 0xa8a7d469, 0x97830e05, 0x113ba7bb]);
+	}
+
 	pub fn add_object(&mut self, obj: Object)
 	{
 		self.new_objects.push(obj);
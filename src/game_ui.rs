@@ -3,10 +3,19 @@ use allegro_font::*;
 use game_state::*;
 use util::dist;
 use announce::create_announcement;
-use map::map_tile_solid;
+use map::{map_tile_solid, freeze_around};
 use unit::create_unit;
 use main_menu::create_main_menu;
 use effect::create_spawn;
+use objective::ObjectiveStatus;
+use trigger::GameEvent;
+use topology::{Topology, neighbour_offsets, tile_dist, tile_to_world};
+use engine::id_map::{IdMap, HasId};
+use craft::{SpellConfig, CURSE_RADIUS};
+use status::add_status;
+use path::{run_planned_orders, clear_orders};
+use turn::start_phase;
+use undo::{save_undo, forget_undo};
 
 const POEM_WIDTH: i32 = 256;
 const POEM_HEIGHT: i32 = 128;
@@ -14,6 +23,38 @@ const Y_OFFSET: i32 = 64;
 const WIDTH: i32 = 64;
 const HEIGHT: i32 = 16;
 const SPACE: i32 = 8;
+// Only this many abilities get a key and a button.
+const MAX_ABILITIES: usize = 5;
+
+fn get_go_button_xy(state: &GameState) -> (i32, i32)
+{
+	(state.buffer.get_width() / 2 - WIDTH / 2, state.buffer.get_height() - HEIGHT - SPACE / 2)
+}
+
+fn can_go(map_data: &MapData) -> bool
+{
+	map_data.num_planned > 0 && map_data.our_turn && !map_data.executing_orders && !map_data.crafting
+}
+
+// The unit whose abilities are on the action bar, if it's time to pick one.
+fn action_bar_unit(objects: &IdMap<Object>, map_data: &MapData) -> Option<usize>
+{
+	if !map_data.our_turn || map_data.executing_orders || map_data.crafting
+	{
+		return None;
+	}
+	objects.elems().iter()
+		.find(|obj| obj.selected && obj.is_ours && obj.can_act && obj.has_pos && !obj.abilities.is_empty())
+		.map(|obj| obj.get_id())
+}
+
+// A row of buttons, centred above the Go button.
+fn get_action_button_xy(idx: usize, num: usize, state: &GameState) -> (i32, i32)
+{
+	let (_, go_y) = get_go_button_xy(state);
+	let row_width = num as i32 * (WIDTH + SPACE) - SPACE;
+	(state.buffer.get_width() / 2 - row_width / 2 + idx as i32 * (WIDTH + SPACE), go_y - HEIGHT - SPACE / 2)
+}
 
 fn get_inventory_xy(idx: usize) -> (i32, i32)
 {
@@ -47,6 +88,24 @@ simple_behavior!
 		
 		state.core.draw_text(&state.ui_font, Color::from_rgba(192, 255, 128, 255),
 			mid_x as f32, 4 as f32, FontAlign::Centre, &format!("MANA: {}", map_data.mana));
+
+		for (i, objective) in map_data.objectives.iter().enumerate()
+		{
+			let color = match objective.status
+			{
+				ObjectiveStatus::InProgress => Color::from_rgba(255, 255, 255, 255),
+				ObjectiveStatus::Complete => Color::from_rgba(192, 255, 128, 255),
+				ObjectiveStatus::Failed => Color::from_rgba(255, 96, 96, 255),
+			};
+			state.core.draw_text(&state.ui_font, color, 36.0, (16 + i * 10) as f32, FontAlign::Left, &objective.describe(&map_data));
+		}
+		
+		if can_go(&map_data)
+		{
+			let (x, y) = get_go_button_xy(state);
+			state.blank_button.as_ref().unwrap().draw(x as f32, y as f32, state);
+			state.core.draw_text(&state.ui_font, Color::from_rgba(255, 255, 255, 255), (x + WIDTH / 2) as f32, (y + 4) as f32, FontAlign::Centre, &format!("Go! ({})", map_data.num_planned));
+		}
 		
 		if map_data.crafting
 		{			
@@ -108,12 +167,27 @@ complex_behavior!
 		if let Some(ref map_data) = map_data
 		{
 			let mut map_data = map_data.borrow_mut();
+			if state.key_down == Some(KeyCode::G) && can_go(&map_data)
+			{
+				run_planned_orders(objects, state.shift_held, &mut map_data);
+			}
 			if let (Some(mouse_x), Some(mouse_y)) = (state.mouse_x, state.mouse_y)
 			{
 				let mouse_x = mouse_x / SCALE as i32;
 				let mouse_y = mouse_y / SCALE as i32;
 				let button = state.mouse_button_down.unwrap_or(0);
-				if dist(mouse_x, mouse_y, 16, 16) < 12
+				let (go_x, go_y) = get_go_button_xy(state);
+				if can_go(&map_data) && mouse_x > go_x && mouse_x < go_x + WIDTH && mouse_y > go_y && mouse_y < go_y + HEIGHT
+				{
+					if button == 1
+					{
+						run_planned_orders(objects, state.shift_held, &mut map_data);
+					}
+					state.mouse_x = None;
+					state.mouse_y = None;
+					state.mouse_button_down = None;
+				}
+				else if dist(mouse_x, mouse_y, 16, 16) < 12
 				{
 					if button == 1
 					{
@@ -129,10 +203,22 @@ complex_behavior!
 				}
 				else if dist(mouse_x, mouse_y, 16, state.buffer.get_height() - 16) < 12
 				{
-					if button == 1
+					// Units that are on the move get to finish first.
+					if button == 1 && !map_data.executing_orders
 					{
 						map_data.our_turn = false;
 						map_data.crafting = false;
+						forget_undo(&mut map_data);
+						// Whatever wasn't run is forgotten.
+						for obj in objects.elems_mut()
+						{
+							if obj.is_ours
+							{
+								clear_orders(obj, map_data.topology);
+								obj.active_ability = None;
+							}
+						}
+						start_phase(objects, false, state);
 					}
 					state.mouse_x = None;
 					state.mouse_y = None;
@@ -153,6 +239,147 @@ complex_behavior!
 	}
 }
 
+// Blessings go to the wizard and the allies next to it, curses to the enemies nearby.
+fn apply_spell_statuses(spell: &SpellConfig, x: i32, y: i32, objects: &mut IdMap<Object>, topology: Topology) -> usize
+{
+	let mut num_affected = 0;
+	for obj in objects.elems_mut()
+	{
+		if !obj.can_act || !obj.has_pos
+		{
+			continue;
+		}
+		let dist = tile_dist(topology, x, y, obj.tile_x, obj.tile_y);
+		let statuses = if obj.is_ours && dist <= 1
+		{
+			&spell.bless
+		}
+		else if !obj.is_ours && dist <= CURSE_RADIUS
+		{
+			&spell.curse
+		}
+		else
+		{
+			continue;
+		};
+		for status in statuses
+		{
+			add_status(obj, status.kind, status.turns);
+		}
+		if !statuses.is_empty()
+		{
+			num_affected += 1;
+		}
+	}
+	num_affected
+}
+
+pub struct ActionBarDraw;
+
+complex_behavior!
+{
+	ActionBarDraw[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let map_data = map_data.borrow();
+			let unit = match action_bar_unit(objects, &map_data).and_then(|id| objects.get(id))
+			{
+				Some(unit) => unit,
+				None => return,
+			};
+			let num = unit.abilities.len().min(MAX_ABILITIES);
+			for (i, ability) in unit.abilities.iter().take(num).enumerate()
+			{
+				let (x, y) = get_action_button_xy(i, num, state);
+				state.blank_button.as_ref().unwrap().draw(x as f32, y as f32, state);
+				let (color, text) = if ability.cooldown > 0
+				{
+					(Color::from_rgba(128, 128, 128, 255), format!("{} ({})", ability.name(), ability.cooldown))
+				}
+				else if unit.active_ability == Some(i)
+				{
+					(Color::from_rgba(255, 224, 64, 255), format!("{} {}", ability.name(), ability.config.cost))
+				}
+				else if ability.config.cost > unit.action_points
+				{
+					(Color::from_rgba(128, 128, 128, 255), format!("{} {}", ability.name(), ability.config.cost))
+				}
+				else
+				{
+					(Color::from_rgba(255, 255, 255, 255), format!("{} {}", ability.name(), ability.config.cost))
+				};
+				state.core.draw_text(&state.ui_font, color, (x + WIDTH / 2) as f32, (y + 4) as f32, FontAlign::Centre, &text);
+			}
+		}
+	}
+}
+
+pub struct ActionBarInput;
+
+complex_behavior!
+{
+	ActionBarInput[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let unit_id = match action_bar_unit(objects, &map_data.borrow())
+			{
+				Some(unit_id) => unit_id,
+				None => return,
+			};
+			let num = objects.get(unit_id).unwrap().abilities.len().min(MAX_ABILITIES);
+			let mut picked = match state.key_down
+			{
+				Some(KeyCode::_1) => Some(0),
+				Some(KeyCode::_2) => Some(1),
+				Some(KeyCode::_3) => Some(2),
+				Some(KeyCode::_4) => Some(3),
+				Some(KeyCode::_5) => Some(4),
+				_ => None,
+			}.filter(|&idx| idx < num);
+			if let (Some(mouse_x), Some(mouse_y)) = (state.mouse_x, state.mouse_y)
+			{
+				let mouse_x = mouse_x / SCALE as i32;
+				let mouse_y = mouse_y / SCALE as i32;
+				let button = state.mouse_button_down.unwrap_or(0);
+				for idx in 0..num
+				{
+					let (x, y) = get_action_button_xy(idx, num, state);
+					if mouse_x > x && mouse_x < x + WIDTH && mouse_y > y && mouse_y < y + HEIGHT
+					{
+						if button == 1
+						{
+							picked = Some(idx);
+						}
+						state.mouse_x = None;
+						state.mouse_y = None;
+						state.mouse_button_down = None;
+						break;
+					}
+				}
+			}
+			if let Some(idx) = picked
+			{
+				let unit = objects.get_mut(unit_id).unwrap();
+				let cooldown = unit.abilities[idx].cooldown;
+				if cooldown > 0
+				{
+					let text = format!("{} isn't ready for {} more turns!", unit.abilities[idx].name(), cooldown);
+					let announce = create_announcement(state.current_map_id, &text, state);
+					state.add_object(announce);
+				}
+				else
+				{
+					unit.active_ability = if unit.active_ability == Some(idx) { None } else { Some(idx) };
+				}
+			}
+		}
+	}
+}
+
 pub struct CraftInput;
 
 complex_behavior!
@@ -238,56 +465,91 @@ complex_behavior!
 						spell.pop();
 						let cost = cost * cost * cost / 36;
 						
-						let summon = state.spells.get(&spell).map(|s| s.clone());
-						let announce_text = if let Some(summon) = summon
+						let mut wizard_x = 0;
+						let mut wizard_y = 0;
+						if let Some(wizard) = objects.get(map_data.wizard_id)
 						{
-							let mut wizard_x = 0;
-							let mut wizard_y = 0;
-							if let Some(wizard) = objects.get(map_data.wizard_id)
-							{
-								wizard_x = wizard.tile_x;
-								wizard_y = wizard.tile_y;
-							}
-							
-							let mut found_pos = None;
-							for m in &[(-1, 0), (1, 0), (0, -1), (0, 1)]
+							wizard_x = wizard.tile_x;
+							wizard_y = wizard.tile_y;
+						}
+						
+						let spell_config = state.spells.get(&spell).cloned();
+						let announce_text = match spell_config
+						{
+							Some(ref spell_config) if spell_config.summon.is_empty() =>
 							{
-								let cand_x = wizard_x + m.0;
-								let cand_y = wizard_y + m.1;
-								if !map_tile_solid(cand_x, cand_y, &*map_data)
+								if cost > map_data.mana
 								{
-									found_pos = Some((cand_x, cand_y));
+									"Not enough mana!".to_string()
 								}
-							}
-							
-							if let Some((x, y)) = found_pos
+								else
+								{
+									save_undo(objects, &mut map_data);
+									let froze = spell_config.freeze > 0 && freeze_around(wizard_x, wizard_y, spell_config.freeze, &mut map_data);
+									let num_affected = apply_spell_statuses(spell_config, wizard_x, wizard_y, objects, map_data.topology);
+									if froze || num_affected > 0
+									{
+										map_data.mana -= cost;
+										state.events.push(GameEvent::SpellCast(spell_config.name.clone()));
+										if froze
+										{
+											"The water freezes over!".to_string()
+										}
+										else
+										{
+											format!("{} takes hold!", spell_config.name)
+										}
+									}
+									else
+									{
+										map_data.undo_stack.pop();
+										if spell_config.freeze > 0 { "Nothing to freeze!" } else { "Nothing happens!" }.to_string()
+									}
+								}
+							},
+							Some(spell_config) =>
 							{
-								if cost <= map_data.mana
+								let mut found_pos = None;
+								for m in neighbour_offsets(map_data.topology, wizard_y)
 								{
-									map_data.mana -= cost;								
-									let mut obj = create_unit(state.current_map_id, &summon, true, state);
-									obj.x = x as f32 * 32.0;
-									obj.y = y as f32 * 32.0;
-									obj.action_points = 0;
-									let msg = format!("Summoned {}!", obj.name);
-									let effect = create_spawn(state.current_map_id, obj.x, obj.y, state);
-									state.add_object(obj);
-									state.add_object(effect);
-									msg
+									let cand_x = wizard_x + m.0;
+									let cand_y = wizard_y + m.1;
+									if !map_tile_solid(cand_x, cand_y, &*map_data)
+									{
+										found_pos = Some((cand_x, cand_y));
+									}
+								}
+								
+								if let Some((x, y)) = found_pos
+								{
+									if cost <= map_data.mana
+									{
+										save_undo(objects, &mut map_data);
+										map_data.mana -= cost;								
+										let mut obj = create_unit(state.current_map_id, &spell_config.summon, true, state);
+										let (world_x, world_y) = tile_to_world(map_data.topology, x, y);
+										obj.x = world_x;
+										obj.y = world_y;
+										obj.action_points = 0;
+										obj.summoned = true;
+										let msg = format!("Summoned {}!", obj.name);
+										state.events.push(GameEvent::SpellCast(obj.name.clone()));
+										let effect = create_spawn(state.current_map_id, obj.x, obj.y, state);
+										state.add_object(obj);
+										state.add_object(effect);
+										msg
+									}
+									else
+									{
+										"Not enough mana!".to_string()
+									}
 								}
 								else
 								{
-									"Not enough mana!".to_string()
+									"No space to summon!".to_string()
 								}
-							}
-							else
-							{
-								"No space to summon!".to_string()
-							}
-						}
-						else
-						{
-							"Gibberish...".to_string()
+							},
+							None => "Gibberish...".to_string(),
 						};
 						let announce = create_announcement(state.current_map_id, &announce_text, state);
 						state.add_object(announce);
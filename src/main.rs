@@ -30,6 +30,19 @@ mod turn;
 mod craft;
 mod announce;
 mod main_menu;
+mod minimap;
+mod camera;
+mod campaign;
+mod objective;
+mod trigger;
+mod tiled;
+mod topology;
+mod pathfind;
+mod undo;
+mod combat;
+mod status;
+mod ability;
+mod experience;
 
 use debug_draw::*;
 use engine::world::*;
@@ -44,6 +57,12 @@ use game_ui::*;
 use turn::*;
 use announce::*;
 use main_menu::*;
+use minimap::*;
+use camera::*;
+use trigger::*;
+use undo::*;
+use status::*;
+use experience::*;
 
 use std::fs::OpenOptions;
 
@@ -95,21 +114,30 @@ fn game()
 	let mut world = World::<Object, GameState>::new(state);
 	
 	world.add_input_behavior(Box::new(UIInput));
+	world.add_input_behavior(Box::new(ActionBarInput));
+	world.add_input_behavior(Box::new(MinimapInput));
 	world.add_input_behavior(Box::new(CraftInput));
+	world.add_input_behavior(Box::new(UndoInput));
 	world.add_input_behavior(Box::new(CursorInput));
 	world.add_input_behavior(Box::new(SelectableInput));
 	world.add_input_behavior(Box::new(PathableInput));
 	world.add_input_behavior(Box::new(CameraInput));
 	world.add_input_behavior(Box::new(MainMenuInput));
+	world.add_input_behavior(Box::new(LevelSelectInput));
 	
+	world.add_logic_behavior(Box::new(InterpLogic));
 	world.add_logic_behavior(Box::new(UnitLogic));
 	world.add_logic_behavior(Box::new(SolidLogic));
+	world.add_logic_behavior(Box::new(VeteranLogic));
 	world.add_logic_behavior(Box::new(OrdersLogic));
+	world.add_logic_behavior(Box::new(ProjectileLogic));
 	world.add_logic_behavior(Box::new(EffectLogic));
 	world.add_logic_behavior(Box::new(HealthDeathLogic));
 	world.add_logic_behavior(Box::new(CameraLogic));
 	world.add_logic_behavior(Box::new(TurnLogic));
+	world.add_logic_behavior(Box::new(PathPreviewLogic));
 	world.add_logic_behavior(Box::new(AnnounceLogic));
+	world.add_logic_behavior(Box::new(TriggerLogic));
 	world.add_logic_behavior(Box::new(InventoryLogic));
 	world.add_logic_behavior(Box::new(VictoryLogic));
 	// Must be last.
@@ -117,19 +145,27 @@ fn game()
 
 	world.add_draw_behavior(Box::new(CameraDraw));
 	world.add_draw_behavior(Box::new(MapDraw));
+	world.add_draw_behavior(Box::new(ObjectiveDraw));
+	world.add_draw_behavior(Box::new(ReachableDraw));
 	world.add_draw_behavior(Box::new(DebugDraw));
 	world.add_draw_behavior(Box::new(MagicCircleDraw));
 	world.add_draw_behavior(Box::new(UnitDraw));
 	world.add_draw_behavior(Box::new(EffectDraw));
+	world.add_draw_behavior(Box::new(ProjectileDraw));
 	world.add_draw_behavior(Box::new(SelectableDraw));
 	world.add_draw_behavior(Box::new(UnitDrawPathable));
+	world.add_draw_behavior(Box::new(StatusDraw));
 	world.add_draw_behavior(Box::new(PathableDraw));
+	world.add_draw_behavior(Box::new(PathPreviewDraw));
 	world.add_draw_behavior(Box::new(CursorDraw));
 	world.add_draw_behavior(Box::new(IdentityTransformDraw));
 	world.add_draw_behavior(Box::new(SelectedDraw));
 	world.add_draw_behavior(Box::new(AnnounceDraw));
+	world.add_draw_behavior(Box::new(MinimapDraw));
 	world.add_draw_behavior(Box::new(UIDraw));
+	world.add_draw_behavior(Box::new(ActionBarDraw));
 	world.add_draw_behavior(Box::new(MainMenuDraw));
+	world.add_draw_behavior(Box::new(LevelSelectDraw));
 	
 	//~ let map = create_map("data/map0.cfg", &mut world.state);
 	//~ world.state.add_object(map);
@@ -145,6 +181,8 @@ fn game()
 			world.state.key_down = None;
 			world.state.key_up = None;
 			world.state.mouse_button_down = None;
+			world.state.mouse_button_up = None;
+			world.state.mouse_dz = 0;
 			world.state.mouse_x = None;
 			world.state.mouse_y = None;
 			match event
@@ -162,17 +200,26 @@ fn game()
 				KeyDown{keycode: k, ..} =>
 				{
 					world.state.key_down = Some(k);
+					if k == KeyCode::LShift || k == KeyCode::RShift
+					{
+						world.state.shift_held = true;
+					}
 					world.input();
 				},
 				KeyUp{keycode: k, ..} =>
 				{
 					world.state.key_up = Some(k);
+					if k == KeyCode::LShift || k == KeyCode::RShift
+					{
+						world.state.shift_held = false;
+					}
 					world.input();
 				},
-				MouseAxes{x, y, ..} =>
+				MouseAxes{x, y, dz, ..} =>
 				{
 					world.state.mouse_x = Some(x);
 					world.state.mouse_y = Some(y);
+					world.state.mouse_dz = dz;
 					world.input();
 				},
 				MouseLeaveDisplay{..} =>
@@ -186,6 +233,13 @@ fn game()
 					world.state.mouse_y = Some(y);
 					world.input();
 				},
+				MouseButtonUp{button, x, y, ..} =>
+				{
+					world.state.mouse_button_up = Some(button);
+					world.state.mouse_x = Some(x);
+					world.state.mouse_y = Some(y);
+					world.input();
+				},
 				TimerTick{count, ..} =>
 				{
 					if !world.state.paused
@@ -2,10 +2,12 @@ use engine::id_map::HasId;
 use game_state::*;
 use allegro::*;
 use allegro_font::*;
-use map::create_map;
+use campaign::{start_campaign_map, is_cleared, is_unlocked};
 
 const WIDTH: i32 = 64;
 const HEIGHT: i32 = 16;
+const ROW_HEIGHT: i32 = 14;
+const LIST_Y: i32 = 48;
 
 pub fn create_main_menu(state: &mut GameState) -> Object
 {
@@ -53,8 +55,8 @@ simple_behavior!
 				let y = mid_y;
 				if mouse_x > x && mouse_x < x + WIDTH && mouse_y > y && mouse_y < y + HEIGHT
 				{
-					let map = create_map("data/map0.cfg", state);
-					state.add_object(map);
+					let level_select = create_level_select(state);
+					state.add_object(level_select);
 					state.remove_object(obj.get_id());
 				}
 				let x = mid_x + WIDTH * 1;
@@ -68,3 +70,92 @@ simple_behavior!
 		break;
 	}
 }
+
+pub fn create_level_select(state: &mut GameState) -> Object
+{
+	let mut obj = Object::new(state.new_id());
+	obj.is_level_select = true;
+	obj
+}
+
+fn get_level_y(idx: usize, state: &GameState) -> i32
+{
+	state.buffer.get_height() / 2 - LIST_Y + idx as i32 * ROW_HEIGHT
+}
+
+fn get_back_xy(state: &GameState) -> (i32, i32)
+{
+	(state.buffer.get_width() / 2 - WIDTH / 2, get_level_y(state.campaign.len(), state) + HEIGHT)
+}
+
+simple_behavior!
+{
+	LevelSelectDraw[obj.is_level_select] |obj, state|
+	{
+		let mid_x = state.buffer.get_width() / 2;
+
+		state.core.draw_text(&state.ui_font, Color::from_rgba(192, 192, 255, 255), mid_x as f32, (get_level_y(0, state) - 2 * ROW_HEIGHT) as f32, FontAlign::Centre, "Select a level");
+		for (i, map) in state.campaign.iter().enumerate()
+		{
+			let (color, text) = if is_cleared(i, state)
+			{
+				(Color::from_rgba(192, 255, 128, 255), format!("{} (cleared)", map.title))
+			}
+			else if is_unlocked(i, state)
+			{
+				(Color::from_rgba(255, 255, 255, 255), map.title.clone())
+			}
+			else
+			{
+				(Color::from_rgba(128, 128, 128, 255), format!("{} (locked)", map.title))
+			};
+			state.core.draw_text(&state.ui_font, color, mid_x as f32, get_level_y(i, state) as f32, FontAlign::Centre, &text);
+		}
+
+		let (x, y) = get_back_xy(state);
+		state.blank_button.as_ref().unwrap().draw(x as f32, y as f32, state);
+		state.core.draw_text(&state.ui_font, Color::from_rgba(255, 192, 192, 255), (x + WIDTH / 2) as f32, (y + 4) as f32, FontAlign::Centre, "Back");
+
+		break;
+	}
+}
+
+simple_behavior!
+{
+	LevelSelectInput[obj.is_level_select] |obj, state|
+	{
+		if let (Some(mouse_x), Some(mouse_y)) = (state.mouse_x, state.mouse_y)
+		{
+			let mouse_x = mouse_x / SCALE as i32;
+			let mouse_y = mouse_y / SCALE as i32;
+			let button = state.mouse_button_down.unwrap_or(0);
+			if button == 1
+			{
+				let mid_x = state.buffer.get_width() / 2;
+				for i in 0..state.campaign.len()
+				{
+					let y = get_level_y(i, state);
+					if mouse_x > mid_x - WIDTH * 2 && mouse_x < mid_x + WIDTH * 2 && mouse_y >= y && mouse_y < y + ROW_HEIGHT
+					{
+						if is_unlocked(i, state)
+						{
+							let map = start_campaign_map(i, state);
+							state.add_object(map);
+							state.remove_object(obj.get_id());
+						}
+						break;
+					}
+				}
+				let (x, y) = get_back_xy(state);
+				if mouse_x > x && mouse_x < x + WIDTH && mouse_y > y && mouse_y < y + HEIGHT
+				{
+					let menu = create_main_menu(state);
+					state.add_object(menu);
+					state.remove_object(obj.get_id());
+				}
+			}
+		}
+
+		break;
+	}
+}
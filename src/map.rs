@@ -3,11 +3,23 @@ use game_state::*;
 use allegro::*;
 use engine::id_map::HasId;
 use std::cmp::{min, max};
+use std::mem;
 use util::populate_from_file;
+use slr_config::{ConfigElement, ElementRepr};
+use tiled::{TiledMap, is_tiled_map, load_tiled};
 use cursor::create_cursor;
 use announce::create_announcement;
 use unit::{create_unit, create_magic_circle};
-use main_menu::create_main_menu;
+use objective::{ObjectiveConfig, ObjectiveKind, ObjectiveStatus, create_objectives, tag_escorts};
+use trigger::{TriggerConfig, GameEvent, create_triggers};
+use camera::get_view_size;
+use topology::{Topology, tile_dist, grid_line, tile_to_world};
+use path::ZoneOfControl;
+use campaign::{start_campaign_map, advance_campaign, mark_cleared, is_last_map, save_veterans};
+use rand::random;
+
+// Size of the cached terrain chunks, in tiles.
+const CHUNK_SIZE: i32 = 8;
 
 slr_def!
 {
@@ -19,13 +31,31 @@ slr_def!
         height: i32 = 32,
         tiles: String = String::new(),
         words: Vec<String> = vec![],
-        next_map: String = String::new()
+        objectives: Vec<ObjectiveConfig> = vec![],
+        triggers: Vec<TriggerConfig> = vec![],
+        topology: Topology = Topology::Square,
+        diagonal_cost: i32 = 2,
+        zone_of_control: ZoneOfControl = ZoneOfControl::Off,
+        // 0 picks a new seed every time the map is played, until it's cleared. After that it's played
+        // again with the seed it was cleared with.
+        seed: i32 = 0,
+        deterministic: i32 = 0
     }
 }
 
+// Thin ice looks like snow on the tilesheet, it gets tinted separately.
+fn terrain_sheet_glyph(tile: char) -> char
+{
+	match tile
+	{
+		'i' => 's',
+		other => other,
+	}
+}
+
 fn tiles_to_index(tl: char, tr: char, br: char, bl: char) -> usize
 {
-	match (tl, tr, br, bl)
+	match (terrain_sheet_glyph(tl), terrain_sheet_glyph(tr), terrain_sheet_glyph(br), terrain_sheet_glyph(bl))
 	{
 		('w', 'w', 'w', 'w') => 0,
 		('s', 's', 's', 's') => 1,
@@ -73,16 +103,36 @@ fn is_solid(tile: char) -> bool
 	match tile
 	{
 		's' => false,
+		'i' => false,
 		'w' => true,
 		_ => true,
 	}
 }
 
+// Like map_tile_solid, but ignores the units.
+pub fn map_terrain_solid(x: i32, y: i32, map_data: &MapData) -> bool
+{
+	let idx = xy_to_index(x, y, map_data.width, map_data.height);
+	x < 1 || y < 1 || x > map_data.width - 2 || y > map_data.height - 2 || is_solid(map_data.tiles[idx])
+}
+
+// Units can walk through their allies, but not their enemies.
+pub fn map_tile_passable(x: i32, y: i32, is_ours: bool, map_data: &MapData) -> bool
+{
+	let idx = xy_to_index(x, y, map_data.width, map_data.height);
+	!map_terrain_solid(x, y, map_data) && map_data.collision_map[idx].is_none_or(|team| team == is_ours)
+}
+
+// Water blocks the line of sight, units don't.
+pub fn line_of_sight(x1: i32, y1: i32, x2: i32, y2: i32, map_data: &MapData) -> bool
+{
+	grid_line(x1, y1, x2, y2).iter().all(|&(x, y)| (x, y) == (x1, y1) || (x, y) == (x2, y2) || !map_terrain_solid(x, y, map_data))
+}
+
 pub fn map_tile_solid(x: i32, y: i32, map_data: &MapData) -> bool
 {
 	let idx = xy_to_index(x, y, map_data.width, map_data.height);
-	return x < 1 || y < 1 || x > map_data.width - 2 || y > map_data.height - 2 || 
-		is_solid(map_data.tiles[idx]) || map_data.collision_map[idx];
+	map_terrain_solid(x, y, map_data) || map_data.collision_map[idx].is_some()
 }
 
 fn load_tilesheet(filename: &str, w: i32, h: i32, state: &mut GameState) -> Vec<SubBitmap>
@@ -102,30 +152,73 @@ fn load_tilesheet(filename: &str, w: i32, h: i32, state: &mut GameState) -> Vec<
 	ret
 }
 
-pub fn create_map(filename: &str, state: &mut GameState) -> Object
+// Map properties are read as if they were entries in a map cfg file.
+fn populate_from_tiled(tiled_map: &TiledMap, map_config: &mut MapConfig)
+{
+	let mut root = ConfigElement::new_table();
+	for (name, value) in &tiled_map.properties
+	{
+		if name == "terrain"
+		{
+			continue;
+		}
+		let elem = ConfigElement::from_str(&format!("{} = {}", name, value)).ok()
+			.and_then(|e| e.as_table().and_then(|t| t.get(name)).cloned())
+			.unwrap_or_else(|| ConfigElement::new_value(value));
+		root.insert(name, elem);
+	}
+	if let Err(errors) = map_config.from_element(&root, None)
+	{
+		for error in errors
+		{
+			warn!("{:?}", error);
+		}
+	}
+	map_config.width = tiled_map.width;
+	map_config.height = tiled_map.height;
+	map_config.tiles = tiled_map.tiles.iter().collect();
+}
+
+const WIZARD_UNIT: &str = "data/wizard.cfg";
+
+// A seed other than 0 takes the place of the map's own.
+pub fn create_map(filename: &str, seed: u32, state: &mut GameState) -> Object
 {
 	let mut obj = Object::new(state.new_id());
 	obj.is_map = true;
 	
 	let mut map_config = MapConfig::new();
-	populate_from_file(filename, &mut map_config).unwrap();
+	let mut tiled_objects = vec![];
+	if is_tiled_map(filename)
+	{
+		let tiled_map = load_tiled(filename).unwrap();
+		populate_from_tiled(&tiled_map, &mut map_config);
+		tiled_objects = tiled_map.objects;
+	}
+	else
+	{
+		populate_from_file(filename, &mut map_config).unwrap();
+	}
 	
 	let mut map_data = MapData::new();
 	map_data.filename = filename.to_string();
-	map_data.next_map = map_config.next_map.clone();
+	// Needed to place the units.
+	map_data.topology = map_config.topology;
+	map_data.objectives = create_objectives(&map_config.objectives);
+	map_data.triggers = create_triggers(&map_config.triggers);
 	
 	for map_char in map_config.tiles.trim().chars().filter(|c| !c.is_whitespace())
 	{
 		let (x, y) = index_to_xy(map_data.tiles.len() as i32, map_config.width);
-		let x = x as f32 * 32.0;
-		let y = y as f32 * 32.0;
+		let (x, y) = tile_to_world(map_data.topology, x, y);
 		let map_char = match map_char
 		{
 			'W' =>
 			{
-				let mut obj = create_unit(obj.get_id(), "data/wizard.cfg", true, state);
+				let mut obj = create_unit(obj.get_id(), WIZARD_UNIT, true, state);
 				obj.x = x;
 				obj.y = y;
+				tag_escorts(&mut map_data.objectives, &obj);
 				map_data.wizard_id = obj.get_id();
 				state.add_object(obj);
 				's'
@@ -135,6 +228,7 @@ pub fn create_map(filename: &str, state: &mut GameState) -> Object
 				let mut obj = create_unit(obj.get_id(), "data/goblin.cfg", false, state);
 				obj.x = x;
 				obj.y = y;
+				tag_escorts(&mut map_data.objectives, &obj);
 				state.add_object(obj);
 				's'
 			},
@@ -143,6 +237,7 @@ pub fn create_map(filename: &str, state: &mut GameState) -> Object
 				let mut obj = create_unit(obj.get_id(), "data/duck.cfg", false, state);
 				obj.x = x;
 				obj.y = y;
+				tag_escorts(&mut map_data.objectives, &obj);
 				state.add_object(obj);
 				's'
 			},
@@ -151,6 +246,7 @@ pub fn create_map(filename: &str, state: &mut GameState) -> Object
 				let mut obj = create_unit(obj.get_id(), "data/duck.cfg", true, state);
 				obj.x = x;
 				obj.y = y;
+				tag_escorts(&mut map_data.objectives, &obj);
 				state.add_object(obj);
 				's'
 			},
@@ -159,6 +255,7 @@ pub fn create_map(filename: &str, state: &mut GameState) -> Object
 				let mut obj = create_unit(obj.get_id(), "data/dragon.cfg", false, state);
 				obj.x = x;
 				obj.y = y;
+				tag_escorts(&mut map_data.objectives, &obj);
 				state.add_object(obj);
 				's'
 			},
@@ -167,6 +264,7 @@ pub fn create_map(filename: &str, state: &mut GameState) -> Object
 				let mut obj = create_unit(obj.get_id(), "data/dragon.cfg", true, state);
 				obj.x = x;
 				obj.y = y;
+				tag_escorts(&mut map_data.objectives, &obj);
 				state.add_object(obj);
 				's'
 			},
@@ -175,6 +273,7 @@ pub fn create_map(filename: &str, state: &mut GameState) -> Object
 				let mut obj = create_unit(obj.get_id(), "data/goblin.cfg", true, state);
 				obj.x = x;
 				obj.y = y;
+				tag_escorts(&mut map_data.objectives, &obj);
 				state.add_object(obj);
 				's'
 			},
@@ -196,118 +295,291 @@ pub fn create_map(filename: &str, state: &mut GameState) -> Object
 		};
 		map_data.tiles.push(map_char);
 	}
+	let mut num_wizards = 0;
+	for tiled_obj in &tiled_objects
+	{
+		let (x, y) = tile_to_world(map_data.topology, tiled_obj.tile_x, tiled_obj.tile_y);
+		let mut new_obj = if let Some(word) = tiled_obj.property("word")
+		{
+			create_magic_circle(obj.get_id(), word, state)
+		}
+		else if let Some(unit) = tiled_obj.property("unit")
+		{
+			let ours = tiled_obj.property("team") == Some("ours");
+			let new_obj = create_unit(obj.get_id(), unit, ours, state);
+			// The flag is for wizards with a cfg of their own.
+			if tiled_obj.property("wizard") == Some("true") || (unit == WIZARD_UNIT && ours)
+			{
+				map_data.wizard_id = new_obj.get_id();
+				num_wizards += 1;
+			}
+			new_obj
+		}
+		else
+		{
+			warn!("Ignoring a Tiled object at {} {} without a 'unit' or 'word' property.", tiled_obj.tile_x, tiled_obj.tile_y);
+			continue;
+		};
+		new_obj.x = x;
+		new_obj.y = y;
+		tag_escorts(&mut map_data.objectives, &new_obj);
+		state.add_object(new_obj);
+	}
+	if is_tiled_map(filename) && num_wizards != 1
+	{
+		panic!("Expected exactly one wizard in {}, found {}! Place a unit with 'unit = {}' and 'team = ours'.", filename, num_wizards, WIZARD_UNIT);
+	}
+	for objective in &map_data.objectives
+	{
+		if objective.config.kind == ObjectiveKind::Escort && objective.escort_id.is_none()
+		{
+			panic!("No allied unit named '{}' to escort in {}!", objective.config.unit, filename);
+		}
+	}
 	map_data.width = map_config.width;
 	map_data.height = map_config.height;
+	map_data.diagonal_cost = map_config.diagonal_cost;
+	map_data.zone_of_control = map_config.zone_of_control;
+	map_data.deterministic = map_config.deterministic != 0;
+	let seed = if seed != 0
+	{
+		seed
+	}
+	else if map_config.seed != 0
+	{
+		map_config.seed as u32
+	}
+	else
+	{
+		random()
+	};
+	info!("Seeding {} with {}", filename, seed);
+	state.seed_rng(seed);
+	map_data.seed = seed;
 	if map_data.height * map_data.width != map_data.tiles.len() as i32 {
 		panic!("Incorrect number of tiles! {} {} vs {}", filename, map_data.tiles.len(), map_data.height * map_data.width);
 	}
-	map_data.collision_map.resize(map_data.tiles.len(), false);
+	map_data.collision_map.resize(map_data.tiles.len(), None);
 	map_data.tilesheet = load_tilesheet(&map_config.tilesheet, 32, 32, state);
+	reset_terrain_chunks(&mut map_data);
 	*obj.map_data.borrow_mut() = map_data;
 	
 	let cursor = create_cursor(obj.get_id(), state);
 	state.add_object(cursor);
 	
 	state.current_map_id = obj.get_id();
+	state.events.clear();
+	state.events.push(GameEvent::TurnStart(1));
 	
-	let announce = create_announcement(obj.get_id(), "Defeat all monsters!", state);
+	let goals: Vec<_> = obj.map_data.borrow().objectives.iter().filter(|o| !o.is_constraint()).map(|o| o.describe(&obj.map_data.borrow())).collect();
+	let announce = create_announcement(obj.get_id(), &format!("{}!", goals.join(", ")), state);
 	state.add_object(announce);
 	
 	obj
 }
 
-simple_behavior!
+fn draw_terrain_cell(x: i32, y: i32, dx: f32, dy: f32, map_data: &MapData, state: &GameState)
 {
-	MapDraw[obj.is_map] |obj, state|
+	let tl = map_data.tiles[xy_to_index(x    , y    , map_data.width, map_data.height)];
+	let tr = map_data.tiles[xy_to_index(x + 1, y    , map_data.width, map_data.height)];
+	let br = map_data.tiles[xy_to_index(x + 1, y + 1, map_data.width, map_data.height)];
+	let bl = map_data.tiles[xy_to_index(x    , y + 1, map_data.width, map_data.height)];
+	let idx = tiles_to_index(tl, tr, br, bl);
+	let bmp = &map_data.tilesheet[idx];
+	state.core.draw_bitmap(bmp, dx, dy, BitmapDrawingFlags::zero());
+}
+
+// On square maps the terrain is drawn in cells between tile centres, so there's one fewer cell than
+// tiles. Hex maps draw every tile whole instead, as the corners of the cells wouldn't line up.
+fn get_num_chunks(map_data: &MapData) -> (i32, i32)
+{
+	let (cells_x, cells_y) = if map_data.topology == Topology::Hex
 	{
-		let map_data = obj.map_data.borrow();
-		state.core.hold_bitmap_drawing(true);
-		for y in 0..map_data.height as i32 - 1
+		(map_data.width, map_data.height)
+	}
+	else
+	{
+		(map_data.width - 1, map_data.height - 1)
+	};
+	((cells_x + CHUNK_SIZE - 1) / CHUNK_SIZE, (cells_y + CHUNK_SIZE - 1) / CHUNK_SIZE)
+}
+
+// Where the first chunk goes in the world, and how much wider than CHUNK_SIZE cells each one is.
+fn get_chunk_layout(map_data: &MapData) -> (f32, f32, i32)
+{
+	if map_data.topology == Topology::Hex
+	{
+		// Room for the shifted odd rows.
+		(-16.0, -16.0, 16)
+	}
+	else
+	{
+		(0.0, 0.0, 0)
+	}
+}
+
+fn build_terrain_chunk(cx: i32, cy: i32, map_data: &MapData, state: &GameState) -> Bitmap
+{
+	let (origin_x, origin_y, extra_w) = get_chunk_layout(map_data);
+	let bmp = Bitmap::new(&state.core, CHUNK_SIZE * 32 + extra_w, CHUNK_SIZE * 32).unwrap();
+	state.core.set_target_bitmap(&bmp);
+	state.core.use_transform(&Transform::identity());
+	state.core.clear_to_color(Color::from_rgba(0, 0, 0, 0));
+	state.core.hold_bitmap_drawing(true);
+	let (chunk_x, chunk_y) = (origin_x + (cx * CHUNK_SIZE * 32) as f32, origin_y + (cy * CHUNK_SIZE * 32) as f32);
+	if map_data.topology == Topology::Hex
+	{
+		for y in cy * CHUNK_SIZE..min((cy + 1) * CHUNK_SIZE, map_data.height)
 		{
-			for x in 0..map_data.width as i32 - 1
+			for x in cx * CHUNK_SIZE..min((cx + 1) * CHUNK_SIZE, map_data.width)
 			{
-				let tl = map_data.tiles[xy_to_index(x    , y    , map_data.width, map_data.height)];
-				let tr = map_data.tiles[xy_to_index(x + 1, y    , map_data.width, map_data.height)];
-				let br = map_data.tiles[xy_to_index(x + 1, y + 1, map_data.width, map_data.height)];
-				let bl = map_data.tiles[xy_to_index(x    , y + 1, map_data.width, map_data.height)];
-				let idx = tiles_to_index(tl, tr, br, bl);
-				let bmp = &map_data.tilesheet[idx];
-				state.core.draw_bitmap(bmp, (x * 32) as f32, (y * 32) as f32, BitmapDrawingFlags::zero());
+				let tile = map_data.tiles[xy_to_index(x, y, map_data.width, map_data.height)];
+				let (wx, wy) = tile_to_world(map_data.topology, x, y);
+				let bmp = &map_data.tilesheet[tiles_to_index(tile, tile, tile, tile)];
+				state.core.draw_bitmap(bmp, wx - 16.0 - chunk_x, wy - 16.0 - chunk_y, BitmapDrawingFlags::zero());
 			}
 		}
-		state.core.hold_bitmap_drawing(false);
-		break;
 	}
-}
-
-simple_behavior!
-{
-	CameraInput[obj.is_map] |obj, state|
+	else
 	{
-		let mut map_data = obj.map_data.borrow_mut();
-		map_data.camera_vx = 0.0;
-		map_data.camera_vy = 0.0;
-		if let (Some(mouse_x), Some(mouse_y)) = (state.mouse_x, state.mouse_y)
+		for y in cy * CHUNK_SIZE..min((cy + 1) * CHUNK_SIZE, map_data.height - 1)
 		{
-			const SPEED: f32 = 256.0;
-			if mouse_x < 8 * SCALE as i32
+			for x in cx * CHUNK_SIZE..min((cx + 1) * CHUNK_SIZE, map_data.width - 1)
 			{
-				map_data.camera_vx = -SPEED;
+				draw_terrain_cell(x, y, ((x - cx * CHUNK_SIZE) * 32) as f32, ((y - cy * CHUNK_SIZE) * 32) as f32, map_data, state);
 			}
-			if mouse_x > state.disp.get_width() - 8 * SCALE as i32
+		}
+	}
+	state.core.hold_bitmap_drawing(false);
+	// Square tiles are centered on the cell corners, so the ones on the far edge poke into this chunk too.
+	let poke = if map_data.topology == Topology::Hex { 0 } else { 1 };
+	for y in cy * CHUNK_SIZE..min((cy + 1) * CHUNK_SIZE + poke, map_data.height)
+	{
+		for x in cx * CHUNK_SIZE..min((cx + 1) * CHUNK_SIZE + poke, map_data.width)
+		{
+			if map_data.tiles[xy_to_index(x, y, map_data.width, map_data.height)] == 'i'
 			{
-				map_data.camera_vx = SPEED;
+				let (wx, wy) = tile_to_world(map_data.topology, x, y);
+				let (dx, dy) = (wx - chunk_x, wy - chunk_y);
+				state.prim.draw_filled_rectangle(dx - 16.0, dy - 16.0, dx + 16.0, dy + 16.0, Color::from_rgba(64, 128, 192, 96));
 			}
-			if mouse_y < 8 * SCALE as i32
+		}
+	}
+	// Each bitmap has its own transform, so the camera transform comes back with the buffer.
+	state.core.set_target_bitmap(&state.buffer);
+	bmp
+}
+
+pub fn reset_terrain_chunks(map_data: &mut MapData)
+{
+	let (num_x, num_y) = get_num_chunks(map_data);
+	map_data.terrain_chunks = (0..num_x * num_y).map(|_| None).collect();
+}
+
+// Changes a tile, making sure the cached terrain gets redrawn.
+pub fn set_tile(x: i32, y: i32, tile: char, map_data: &mut MapData)
+{
+	if x < 0 || y < 0 || x >= map_data.width || y >= map_data.height
+	{
+		return;
+	}
+	let idx = xy_to_index(x, y, map_data.width, map_data.height);
+	if map_data.tiles[idx] == tile
+	{
+		return;
+	}
+	map_data.tiles[idx] = tile;
+	map_data.generation = map_data.generation.wrapping_add(1);
+	// A tile touches the four cells around its centre, or just its own on a hex map.
+	let (num_x, num_y) = get_num_chunks(map_data);
+	let first = if map_data.topology == Topology::Hex { 0 } else { 1 };
+	for cell_y in y - first..y + 1
+	{
+		for cell_x in x - first..x + 1
+		{
+			if cell_x < 0 || cell_y < 0
 			{
-				map_data.camera_vy = -SPEED;
+				continue;
 			}
-			if mouse_y > state.disp.get_height() - 8 * SCALE as i32
+			let (cx, cy) = (cell_x / CHUNK_SIZE, cell_y / CHUNK_SIZE);
+			if cx < num_x && cy < num_y
 			{
-				map_data.camera_vy = SPEED;
+				map_data.terrain_chunks[(cy * num_x + cx) as usize] = None;
 			}
-			break;
 		}
 	}
 }
 
-simple_behavior!
+// Fire melts snow into thin ice, which breaks into water once it's walked off.
+pub fn melt_tile(x: i32, y: i32, map_data: &mut MapData)
 {
-	CameraDraw[obj.is_map] |obj, state|
+	if map_data.tiles[xy_to_index(x, y, map_data.width, map_data.height)] == 's'
 	{
-		let map_data = obj.map_data.borrow();
-		let mut trans = Transform::identity();
-		trans.translate(-map_data.camera_x.floor(), -map_data.camera_y.floor());
-		state.core.use_transform(&trans);
-		break;
+		set_tile(x, y, 'i', map_data);
 	}
 }
 
-simple_behavior!
+pub fn break_ice(x: i32, y: i32, map_data: &mut MapData)
 {
-	CameraLogic[obj.is_map] |obj, state|
+	if map_data.tiles[xy_to_index(x, y, map_data.width, map_data.height)] == 'i'
 	{
-		let mut map_data = obj.map_data.borrow_mut();
-		map_data.camera_x += DT * map_data.camera_vx;
-		map_data.camera_y += DT * map_data.camera_vy;
-		let max_x = ((map_data.width - 1) * 32 as i32 - state.buffer.get_width()) as f32;
-		let max_y = ((map_data.height - 1) * 32 as i32 - state.buffer.get_height()) as f32;
-		if map_data.camera_x > max_x
-		{
-			map_data.camera_x = max_x;
-		}
-		if map_data.camera_y > max_y
+		set_tile(x, y, 'w', map_data);
+	}
+}
+
+// Freezes the water within the radius into snow, leaving the map border alone.
+pub fn freeze_around(x: i32, y: i32, radius: i32, map_data: &mut MapData) -> bool
+{
+	let mut froze = false;
+	for ty in max(1, y - radius)..min(map_data.height - 1, y + radius + 1)
+	{
+		for tx in max(1, x - radius)..min(map_data.width - 1, x + radius + 1)
 		{
-			map_data.camera_y = max_y;
+			if tile_dist(map_data.topology, x, y, tx, ty) <= radius && map_data.tiles[xy_to_index(tx, ty, map_data.width, map_data.height)] == 'w'
+			{
+				set_tile(tx, ty, 's', map_data);
+				froze = true;
+			}
 		}
-		if map_data.camera_x < 0.0
+	}
+	froze
+}
+
+simple_behavior!
+{
+	MapDraw[obj.is_map] |obj, state|
+	{
+		let mut map_data = obj.map_data.borrow_mut();
+		let (num_x, num_y) = get_num_chunks(&map_data);
+		let (view_w, view_h) = get_view_size(&map_data, state);
+		let (origin_x, origin_y, extra_w) = get_chunk_layout(&map_data);
+		let chunk_px = (CHUNK_SIZE * 32) as f32;
+		let min_cx = max(0, ((map_data.camera_x - origin_x - extra_w as f32) / chunk_px).floor() as i32);
+		let min_cy = max(0, ((map_data.camera_y - origin_y) / chunk_px).floor() as i32);
+		let max_cx = min(num_x - 1, ((map_data.camera_x + view_w - origin_x) / chunk_px).floor() as i32);
+		let max_cy = min(num_y - 1, ((map_data.camera_y + view_h - origin_y) / chunk_px).floor() as i32);
+		for cy in min_cy..max_cy + 1
 		{
-			map_data.camera_x = 0.0;
+			for cx in min_cx..max_cx + 1
+			{
+				let idx = (cy * num_x + cx) as usize;
+				if map_data.terrain_chunks[idx].is_none()
+				{
+					let bmp = build_terrain_chunk(cx, cy, &map_data, state);
+					map_data.terrain_chunks[idx] = Some(bmp);
+				}
+			}
 		}
-		if map_data.camera_y < 0.0
+		state.core.hold_bitmap_drawing(true);
+		for cy in min_cy..max_cy + 1
 		{
-			map_data.camera_y = 0.0;
+			for cx in min_cx..max_cx + 1
+			{
+				let bmp = map_data.terrain_chunks[(cy * num_x + cx) as usize].as_ref().unwrap();
+				state.core.draw_bitmap(bmp, origin_x + cx as f32 * chunk_px, origin_y + cy as f32 * chunk_px, BitmapDrawingFlags::zero());
+			}
 		}
+		state.core.hold_bitmap_drawing(false);
 		break;
 	}
 }
@@ -355,25 +627,49 @@ complex_behavior!
 		if let Some(ref map_data) = map_data
 		{
 			let mut map_data = map_data.borrow_mut();
-			if objects.get(map_data.wizard_id).is_none() && !map_data.want_restart
+			let done = map_data.want_restart || map_data.want_next_map;
+			let mut objectives = mem::take(&mut map_data.objectives);
+			for objective in &mut objectives
+			{
+				objective.update(objects, &map_data);
+			}
+			map_data.objectives = objectives;
+
+			let mut failed = map_data.objectives.iter().find(|o| o.status == ObjectiveStatus::Failed).map(|o| o.describe(&map_data));
+			let mut won = map_data.objectives.iter().all(|o| o.is_constraint() || o.status == ObjectiveStatus::Complete);
+			match map_data.scripted_end
+			{
+				Some(true) => won = true,
+				Some(false) => failed = Some("The tale ends here".to_string()),
+				None => ()
+			}
+			if !done && objects.get(map_data.wizard_id).is_none()
 			{
 				let announce = create_announcement(state.current_map_id, "You have died!", state);
 				state.add_object(announce);
 				map_data.want_restart = true;
 				map_data.change_time = state.time + 4.0;
 			}
-			let num_enemies = objects.elems().iter().filter(|obj| !obj.is_ours && obj.can_act).count();
-			if num_enemies == 0 && !map_data.want_next_map
+			else if !done && failed.is_some()
+			{
+				let announce = create_announcement(state.current_map_id, &format!("Failed: {}!", failed.unwrap()), state);
+				state.add_object(announce);
+				map_data.want_restart = true;
+				map_data.change_time = state.time + 4.0;
+			}
+			else if !done && won
 			{
-				let text = if map_data.next_map.is_empty()
+				mark_cleared(map_data.campaign_idx, map_data.seed, state);
+				save_veterans(objects, &map_data, state);
+				let text = if is_last_map(map_data.campaign_idx, state)
 				{
-					"You've won the game!"
+					"You've won the game!".to_string()
 				}
 				else
 				{
-					"Victory!"
+					"Victory! Objectives complete.".to_string()
 				};
-				let announce = create_announcement(state.current_map_id, text, state);
+				let announce = create_announcement(state.current_map_id, &text, state);
 				state.add_object(announce);
 				map_data.want_next_map = true;
 				map_data.change_time = state.time + 4.0;
@@ -382,25 +678,54 @@ complex_behavior!
 			{
 				let old_map_id = state.current_map_id;
 				state.remove_object(old_map_id);
-				let same_map = create_map(&map_data.filename, state);
+				let same_map = start_campaign_map(map_data.campaign_idx, state);
 				state.add_object(same_map);
 			}
 			if map_data.want_next_map && state.time > map_data.change_time
 			{
 				let old_map_id = state.current_map_id;
 				state.remove_object(old_map_id);
-				if map_data.next_map.is_empty()
-				{
-					info!("Going back to menu!");
-					let menu = create_main_menu(state);
-					state.add_object(menu);
-				}
-				else
+				let next = advance_campaign(map_data.campaign_idx, state);
+				state.add_object(next);
+			}
+		}
+	}
+}
+
+pub struct ObjectiveDraw;
+
+complex_behavior!
+{
+	ObjectiveDraw[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let map_data = map_data.borrow();
+			for objective in &map_data.objectives
+			{
+				if objective.config.kind == ObjectiveKind::ReachExit
 				{
-					let new_map = create_map(&map_data.next_map, state);
-					state.add_object(new_map);
+					let (x, y) = tile_to_world(map_data.topology, objective.config.x, objective.config.y);
+					state.prim.draw_rectangle(x - 14.0, y - 14.0, x + 14.0, y + 14.0, Color::from_rgb(255, 255, 0), 2.0);
 				}
 			}
 		}
 	}
 }
+
+#[test]
+fn tiled_properties()
+{
+	for filename in &["data/tiled_sample.tmx", "data/tiled_sample.json"]
+	{
+		let mut map_config = MapConfig::new();
+		populate_from_tiled(&load_tiled(filename).unwrap(), &mut map_config);
+		assert_eq!("data/terrain_sheet.png", map_config.tilesheet);
+		assert_eq!(5, map_config.seed);
+		assert_eq!(ZoneOfControl::Stop, map_config.zone_of_control);
+		assert_eq!(vec!["frost".to_string(), "fire".to_string()], map_config.words);
+		assert_eq!((4, 3), (map_config.width, map_config.height));
+		assert_eq!("wsswwsiwwssw", map_config.tiles);
+	}
+}
@@ -0,0 +1,144 @@
+use allegro::*;
+use game_state::*;
+use std::cmp::max;
+use camera::{get_view_size, center_camera_on};
+use map::index_to_xy;
+use topology::{Topology, row_shift, tile_to_world, world_to_tile};
+
+const MAX_SIZE: i32 = 48;
+const MARGIN: i32 = 4;
+
+// Returns the top-left corner of the minimap and the size of a single tile on it.
+fn get_minimap_xy(map_data: &MapData, state: &GameState) -> (i32, i32, i32)
+{
+	let tile_size = max(1, MAX_SIZE / max(1, max(map_data.width, map_data.height)));
+	let x = MARGIN;
+	let y = state.buffer.get_height() - state.turn_button.as_ref().unwrap().get_height() - MARGIN - map_data.height * tile_size;
+	(x, y, tile_size)
+}
+
+// The odd rows of a hex map stick out half a tile further.
+fn get_minimap_width(map_data: &MapData, tile_size: i32) -> f32
+{
+	let shift = if map_data.topology == Topology::Hex { 0.5 } else { 0.0 };
+	(map_data.width as f32 + shift) * tile_size as f32
+}
+
+// Where a tile's top-left corner goes on the minimap.
+fn tile_on_minimap(x: i32, y: i32, mm_x: i32, mm_y: i32, tile_size: i32, map_data: &MapData) -> (f32, f32)
+{
+	let ts = tile_size as f32;
+	(mm_x as f32 + (x as f32 + row_shift(map_data.topology, y)) * ts, (mm_y + y * tile_size) as f32)
+}
+
+fn tile_color(tile: char) -> Color
+{
+	match tile
+	{
+		's' => Color::from_rgb(224, 224, 240),
+		'i' => Color::from_rgb(160, 192, 224),
+		'w' => Color::from_rgb(32, 64, 160),
+		_ => Color::from_rgb(0, 0, 0),
+	}
+}
+
+pub struct MinimapDraw;
+
+complex_behavior!
+{
+	MinimapDraw[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let map_data = map_data.borrow();
+			let (mm_x, mm_y, tile_size) = get_minimap_xy(&map_data, state);
+			let ts = tile_size as f32;
+
+			state.prim.draw_filled_rectangle((mm_x - 1) as f32, (mm_y - 1) as f32,
+				mm_x as f32 + get_minimap_width(&map_data, tile_size) + 1.0, (mm_y + map_data.height * tile_size + 1) as f32,
+				Color::from_rgb(0, 0, 0));
+			for (idx, &tile) in map_data.tiles.iter().enumerate()
+			{
+				let (x, y) = index_to_xy(idx as i32, map_data.width);
+				let (x, y) = tile_on_minimap(x, y, mm_x, mm_y, tile_size, &map_data);
+				state.prim.draw_filled_rectangle(x, y, x + ts, y + ts, tile_color(tile));
+			}
+
+			for obj in objects.elems()
+			{
+				if !obj.has_pos
+				{
+					continue;
+				}
+				let color = if obj.is_magic_circle
+				{
+					Color::from_rgb(192, 64, 255)
+				}
+				else if obj.can_act && obj.is_ours
+				{
+					Color::from_rgb(64, 255, 64)
+				}
+				else if obj.can_act
+				{
+					Color::from_rgb(255, 32, 32)
+				}
+				else
+				{
+					continue;
+				};
+				let (x, y) = tile_on_minimap(obj.tile_x, obj.tile_y, mm_x, mm_y, tile_size, &map_data);
+				state.prim.draw_filled_rectangle(x, y, x + ts, y + ts, color);
+			}
+
+			let cam_x = mm_x as f32 + map_data.camera_x * ts / 32.0;
+			let cam_y = mm_y as f32 + map_data.camera_y * ts / 32.0;
+			let (view_w, view_h) = get_view_size(&map_data, state);
+			let cam_w = view_w * ts / 32.0;
+			let cam_h = view_h * ts / 32.0;
+			state.prim.draw_rectangle(cam_x + 0.5, cam_y + 0.5, cam_x + cam_w - 0.5, cam_y + cam_h - 0.5,
+				Color::from_rgb(255, 255, 0), 1.0);
+		}
+	}
+}
+
+pub struct MinimapInput;
+
+complex_behavior!
+{
+	MinimapInput[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let mut map_data = map_data.borrow_mut();
+			if map_data.crafting
+			{
+				return;
+			}
+			if let (Some(mouse_x), Some(mouse_y)) = (state.mouse_x, state.mouse_y)
+			{
+				let mouse_x = mouse_x / SCALE as i32;
+				let mouse_y = mouse_y / SCALE as i32;
+				let (mm_x, mm_y, tile_size) = get_minimap_xy(&map_data, state);
+				if mouse_x >= mm_x && (mouse_x as f32) < mm_x as f32 + get_minimap_width(&map_data, tile_size) &&
+					mouse_y >= mm_y && mouse_y < mm_y + map_data.height * tile_size
+				{
+					if state.mouse_button_down.unwrap_or(0) == 1
+					{
+						// Centre the camera on the clicked tile, CameraLogic will do the clamping.
+						// Each tile covers the world half a tile either side of its centre.
+						let world_x = (mouse_x - mm_x) as f32 * 32.0 / tile_size as f32 - 16.0;
+						let world_y = (mouse_y - mm_y) as f32 * 32.0 / tile_size as f32 - 16.0;
+						let (tile_x, tile_y) = world_to_tile(map_data.topology, world_x, world_y);
+						let (x, y) = tile_to_world(map_data.topology, tile_x, tile_y);
+						center_camera_on(x, y, &mut map_data, state);
+					}
+					state.mouse_x = None;
+					state.mouse_y = None;
+					state.mouse_button_down = None;
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,207 @@
+use engine::id_map::{IdMap, HasId};
+use game_state::*;
+
+slr_def!
+{
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	pub enum ObjectiveKind
+	{
+		DefeatAll,
+		Survive,
+		ReachExit,
+		HoldCircles,
+		Escort,
+		TurnLimit
+	}
+}
+
+slr_def!
+{
+	#[derive(Clone, Debug)]
+    pub struct ObjectiveConfig
+    {
+        kind: ObjectiveKind = ObjectiveKind::DefeatAll,
+        turns: i32 = 0,
+        x: i32 = 0,
+        y: i32 = 0,
+        // For Escort, the name of the allied unit to keep alive.
+        unit: String = String::new()
+    }
+}
+
+impl Default for ObjectiveConfig
+{
+	fn default() -> ObjectiveConfig
+	{
+		ObjectiveConfig::new()
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ObjectiveStatus
+{
+	InProgress,
+	Complete,
+	Failed,
+}
+
+#[derive(Clone, Debug)]
+pub struct Objective
+{
+	pub config: ObjectiveConfig,
+	pub status: ObjectiveStatus,
+	// Consecutive turns all the magic circles were held for.
+	pub turns_held: i32,
+	last_turn: i32,
+	pub escort_id: Option<usize>,
+}
+
+// Escort objectives follow the first of our units with their name, called as the map's units are made.
+pub fn tag_escorts(objectives: &mut [Objective], unit: &Object)
+{
+	if !unit.is_ours
+	{
+		return;
+	}
+	for objective in objectives
+	{
+		if objective.config.kind == ObjectiveKind::Escort && objective.escort_id.is_none() && objective.config.unit == unit.name
+		{
+			objective.escort_id = Some(unit.get_id());
+		}
+	}
+}
+
+impl Objective
+{
+	pub fn new(config: ObjectiveConfig) -> Objective
+	{
+		Objective
+		{
+			config,
+			status: ObjectiveStatus::InProgress,
+			turns_held: 0,
+			last_turn: 1,
+			escort_id: None,
+		}
+	}
+
+	// Constraints only ever fail, the map is won once every other objective is complete.
+	pub fn is_constraint(&self) -> bool
+	{
+		matches!(self.config.kind, ObjectiveKind::Escort | ObjectiveKind::TurnLimit)
+	}
+
+	pub fn describe(&self, map_data: &MapData) -> String
+	{
+		let c = &self.config;
+		match c.kind
+		{
+			ObjectiveKind::DefeatAll => "Defeat all monsters".to_string(),
+			ObjectiveKind::Survive => format!("Survive {} turns ({}/{})", c.turns, (map_data.turn - 1).min(c.turns), c.turns),
+			ObjectiveKind::ReachExit => format!("Reach the exit at {}, {}", c.x, c.y),
+			ObjectiveKind::HoldCircles => format!("Hold all circles for {} turns ({}/{})", c.turns, self.turns_held, c.turns),
+			ObjectiveKind::Escort => format!("Keep the {} alive", c.unit),
+			ObjectiveKind::TurnLimit => format!("Win by turn {}", c.turns),
+		}
+	}
+
+	pub fn update(&mut self, objects: &IdMap<Object>, map_data: &MapData)
+	{
+		if self.status != ObjectiveStatus::InProgress
+		{
+			return;
+		}
+		let c = &self.config;
+		let new_turn = map_data.turn != self.last_turn;
+		self.last_turn = map_data.turn;
+		self.status = match c.kind
+		{
+			ObjectiveKind::DefeatAll =>
+			{
+				if objects.elems().iter().any(|obj| !obj.is_ours && obj.can_act)
+				{
+					ObjectiveStatus::InProgress
+				}
+				else
+				{
+					ObjectiveStatus::Complete
+				}
+			},
+			ObjectiveKind::Survive =>
+			{
+				if map_data.turn > c.turns
+				{
+					ObjectiveStatus::Complete
+				}
+				else
+				{
+					ObjectiveStatus::InProgress
+				}
+			},
+			ObjectiveKind::ReachExit =>
+			{
+				if objects.elems().iter().any(|obj| obj.is_ours && obj.can_act && obj.has_pos && obj.tile_x == c.x && obj.tile_y == c.y)
+				{
+					ObjectiveStatus::Complete
+				}
+				else
+				{
+					ObjectiveStatus::InProgress
+				}
+			},
+			ObjectiveKind::HoldCircles =>
+			{
+				if new_turn
+				{
+					let num_circles = objects.elems().iter().filter(|obj| obj.is_magic_circle).count() as i32;
+					if num_circles > 0 && map_data.num_circles_held >= num_circles
+					{
+						self.turns_held += 1;
+					}
+					else
+					{
+						self.turns_held = 0;
+					}
+				}
+				if self.turns_held >= c.turns
+				{
+					ObjectiveStatus::Complete
+				}
+				else
+				{
+					ObjectiveStatus::InProgress
+				}
+			},
+			ObjectiveKind::Escort =>
+			{
+				match self.escort_id.and_then(|id| objects.get(id))
+				{
+					Some(escort) if escort.can_act && escort.health > 0 => ObjectiveStatus::InProgress,
+					_ => ObjectiveStatus::Failed,
+				}
+			},
+			ObjectiveKind::TurnLimit =>
+			{
+				if map_data.turn > c.turns
+				{
+					ObjectiveStatus::Failed
+				}
+				else
+				{
+					ObjectiveStatus::InProgress
+				}
+			},
+		};
+	}
+}
+
+pub fn create_objectives(configs: &[ObjectiveConfig]) -> Vec<Objective>
+{
+	let mut ret: Vec<_> = configs.iter().map(|c| Objective::new(c.clone())).collect();
+	if ret.iter().all(|o| o.is_constraint())
+	{
+		ret.insert(0, Objective::new(ObjectiveConfig::new()));
+	}
+	ret
+}
@@ -1,21 +1,245 @@
-use std::collections::{HashMap, BinaryHeap};
+use std::collections::HashMap;
 use engine::id_map::{IdMap, HasId};
-use map::map_tile_solid;
+use map::{xy_to_index, line_of_sight, map_tile_solid, map_tile_passable, map_terrain_solid, melt_tile, break_ice};
 use game_state::*;
-use effect::{create_slash, create_fire};
-use util::l1_dist;
+use allegro::*;
+use allegro_font::*;
+use effect::{create_slash, create_fire, create_projectile};
+use topology::{Topology, neighbour_offsets, tile_dist, step_cost, is_diagonal_step, tile_to_world, world_to_tile};
+use pathfind::{Grid, Passability};
+use undo::{save_undo, forget_undo};
+use combat::{DamageType, Attack, Roll, damage_taken, roll_attack};
+use announce::create_announcement;
+use status::{StatusKind, has_status, add_status, remove_status};
+use ability::{can_target, use_ability};
+use experience::{gain_experience, KILL_EXPERIENCE};
+
+slr_def!
+{
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	pub enum ZoneOfControl
+	{
+		Off,
+		// Moving next to an enemy ends the move.
+		Stop,
+		// Moving next to an enemy uses up all the action points, so the unit can't attack either.
+		Drain
+	}
+}
 
 pub struct PathableInput;
 
+// Where the selected unit would go if ordered to the cursor.
+pub struct PathPreview
+{
+	unit_id: usize,
+	tile_x: i32,
+	tile_y: i32,
+	action_points: i32,
+	cursor_x: i32,
+	cursor_y: i32,
+	num_orders: usize,
+	adding_waypoint: bool,
+	ability: Option<usize>,
+	generation: u32,
+	pub orders: Vec<Order>,
+}
+
+pub fn orders_cost(orders: &[Order]) -> i32
+{
+	orders.iter().map(|o| o.cost).sum()
+}
+
 pub fn order_unit(obj_id: usize, objects: &mut IdMap<Object>, goal_x: i32, goal_y: i32, map_data: &mut MapData)
 {
-	let mut attack_order = false;
-	let order_ours = objects.get(obj_id).unwrap().is_ours;
+	let orders = plan_orders(obj_id, objects, goal_x, goal_y, map_data);
+	set_orders(objects.get_mut(obj_id).unwrap(), orders, map_data.topology);
+}
+
+// Like order_unit, but keeps the current orders and adds a path from their end.
+pub fn add_waypoint(obj_id: usize, objects: &mut IdMap<Object>, goal_x: i32, goal_y: i32, map_data: &mut MapData)
+{
+	let orders = plan_waypoint_orders(obj_id, objects, goal_x, goal_y, map_data);
+	set_orders(objects.get_mut(obj_id).unwrap(), orders, map_data.topology);
+}
+
+fn set_orders(obj: &mut Object, orders: Vec<Order>, topology: Topology)
+{
+	// The ability is picked for one order at a time.
+	if orders.last().is_some_and(|o| matches!(o.order_type, OrderType::Ability(_)))
+	{
+		obj.active_ability = None;
+	}
+	replace_orders(obj, orders, topology);
+}
+
+// A unit caught half way through a step is put down on the tile it's over, paying for the step if
+// that's where it was going. Otherwise its next step would start from where the last one did.
+pub fn replace_orders(obj: &mut Object, orders: Vec<Order>, topology: Topology)
+{
+	if obj.step_progress > 0.0
+	{
+		let (tile_x, tile_y) = world_to_tile(topology, obj.x, obj.y);
+		if let Some(order) = obj.orders.first()
+		{
+			if matches!(order.order_type, OrderType::MoveTo) && (order.x, order.y) == (tile_x, tile_y)
+			{
+				obj.action_points = (obj.action_points - order.cost).max(0);
+			}
+		}
+		let (x, y) = tile_to_world(topology, tile_x, tile_y);
+		obj.x = x;
+		obj.y = y;
+		obj.tile_x = tile_x;
+		obj.tile_y = tile_y;
+		obj.step_progress = 0.0;
+	}
+	obj.orders = orders;
+}
+
+pub fn clear_orders(obj: &mut Object, topology: Topology)
+{
+	replace_orders(obj, vec![], topology);
+}
+
+// Where the orders leave the unit, if they move it at all.
+fn last_stop(orders: &[Order]) -> Option<(i32, i32)>
+{
+	orders.iter().rev().find(|o| matches!(o.order_type, OrderType::MoveTo)).map(|o| (o.x, o.y))
+}
+
+fn has_planned_move(obj: &Object) -> bool
+{
+	obj.is_ours && obj.can_act && obj.action_points > 0 && last_stop(&obj.orders).is_some()
+}
+
+// Starts the orders of all our units that have some. Two units can't end up on the same tile, so
+// the paths of the later ones are cut short where they would. With in_sequence, the units go one
+// at a time in the order they were created, otherwise all at once.
+pub fn run_planned_orders(objects: &mut IdMap<Object>, in_sequence: bool, map_data: &mut MapData)
+{
+	save_undo(objects, map_data);
+	loop
+	{
+		let mut reserved: Vec<(i32, i32)> = objects.elems().iter()
+			.filter(|obj| obj.is_solid && obj.has_pos && !has_planned_move(obj))
+			.map(|obj| (obj.tile_x, obj.tile_y))
+			.collect();
+		let movers: Vec<(usize, (i32, i32))> = objects.elems().iter()
+			.filter(|obj| has_planned_move(obj))
+			.map(|obj| (obj.get_id(), (obj.tile_x, obj.tile_y)))
+			.collect();
+		let mut trimmed = false;
+		for obj in objects.elems_mut()
+		{
+			if !has_planned_move(obj)
+			{
+				continue;
+			}
+			// One at a time, the units that go later are still standing where they are.
+			let waiting: Vec<(i32, i32)> = if in_sequence
+			{
+				movers.iter().skip_while(|m| m.0 != obj.get_id()).skip(1).map(|m| m.1).collect()
+			}
+			else
+			{
+				vec![]
+			};
+			while last_stop(&obj.orders).is_some_and(|t| reserved.contains(&t) || waiting.contains(&t))
+			{
+				obj.orders.pop();
+				trimmed = true;
+			}
+			reserved.push(last_stop(&obj.orders).unwrap_or((obj.tile_x, obj.tile_y)));
+		}
+		// A unit that can't move anymore keeps its tile, which someone else may have been heading for.
+		if !trimmed
+		{
+			break;
+		}
+	}
+
+	let mut ids = vec![];
 	for obj in objects.elems_mut()
 	{
-		if obj.has_health && order_ours != obj.is_ours && obj.has_pos
+		if obj.is_ours && obj.can_act && obj.action_points > 0 && !obj.orders.is_empty()
+		{
+			if !in_sequence
+			{
+				obj.executing_orders = true;
+			}
+			ids.push(obj.get_id());
+		}
+	}
+	info!("Running the orders of {} units", ids.len());
+	map_data.executing_orders = !ids.is_empty();
+	if in_sequence
+	{
+		map_data.order_queue = ids;
+	}
+}
+
+// The orders that order_unit would give, without giving them.
+pub fn plan_orders(obj_id: usize, objects: &IdMap<Object>, goal_x: i32, goal_y: i32, map_data: &MapData) -> Vec<Order>
+{
+	let obj = objects.get(obj_id).unwrap();
+	plan_segment(obj, objects, (obj.tile_x, obj.tile_y), obj.action_points, goal_x, goal_y, map_data)
+}
+
+// The orders that add_waypoint would give, without giving them.
+pub fn plan_waypoint_orders(obj_id: usize, objects: &IdMap<Object>, goal_x: i32, goal_y: i32, map_data: &MapData) -> Vec<Order>
+{
+	let obj = objects.get(obj_id).unwrap();
+	let mut orders = obj.orders.clone();
+	// Nothing can follow an attack or an ability.
+	if orders.last().is_some_and(|o| !matches!(o.order_type, OrderType::MoveTo))
+	{
+		return orders;
+	}
+	let start = orders.last().map_or((obj.tile_x, obj.tile_y), |o| (o.x, o.y));
+	// Neither can anything follow walking into a zone of control.
+	if !orders.is_empty() && UnitPassability::new(obj, map_data).ends_move(start.0, start.1)
+	{
+		return orders;
+	}
+	let action_points = obj.action_points - orders_cost(&orders);
+	orders.extend(plan_segment(obj, objects, start, action_points, goal_x, goal_y, map_data));
+	orders
+}
+
+fn plan_segment(obj: &Object, objects: &IdMap<Object>, start: (i32, i32), action_points: i32, goal_x: i32, goal_y: i32, map_data: &MapData) -> Vec<Order>
+{
+	if let Some(idx) = obj.active_ability
+	{
+		// Walk to the cheapest tile to use the ability from, or nowhere if there isn't one.
+		let ability = &obj.abilities[idx];
+		let cost = ability.config.cost;
+		let spot = unit_reachable_tiles(obj, start, action_points, map_data).into_iter()
+			.filter(|&(x, y, path_cost)| path_cost + cost <= action_points && can_target(obj, ability, (x, y), goal_x, goal_y, objects, map_data))
+			.min_by_key(|t| t.2);
+		return match spot
+		{
+			Some((x, y, _)) =>
+			{
+				let mut orders = make_path(obj, start.0, start.1, action_points, x, y, map_data);
+				orders.push(Order
+				{
+					order_type: OrderType::Ability(idx),
+					x: goal_x,
+					y: goal_y,
+					cost,
+				});
+				orders
+			},
+			None => vec![],
+		};
+	}
+	let mut attack_order = false;
+	for other in objects.elems()
+	{
+		if other.has_health && obj.is_ours != other.is_ours && other.has_pos
 		{
-			if obj.tile_x == goal_x && obj.tile_y == goal_y
+			if other.tile_x == goal_x && other.tile_y == goal_y
 			{
 				attack_order = true;
 				break;
@@ -23,135 +247,186 @@ pub fn order_unit(obj_id: usize, objects: &mut IdMap<Object>, goal_x: i32, goal_
 		}
 	}
 	
-	let obj = objects.get_mut(obj_id).unwrap();
-	make_path(obj, goal_x, goal_y, &mut *map_data);
-	let last_pos = obj.orders.last().map_or((obj.tile_x, obj.tile_y), |o| (o.x, o.y));
-	if obj.orders.len() < obj.action_points as usize && attack_order && l1_dist(goal_x, goal_y, last_pos.0, last_pos.1) == 1
+	if attack_order && obj.max_range > 1
+	{
+		// Walk to the cheapest tile to shoot from, or closer to the target if there isn't one.
+		let shooting_spot = unit_reachable_tiles(obj, start, action_points, map_data).into_iter()
+			.filter(|&(x, y, cost)| cost < action_points && in_attack_range(obj, x, y, goal_x, goal_y, map_data))
+			.min_by_key(|t| t.2);
+		return match shooting_spot
+		{
+			Some((x, y, _)) =>
+			{
+				let mut orders = make_path(obj, start.0, start.1, action_points, x, y, map_data);
+				orders.push(Order
+				{
+					order_type: OrderType::RangedAttack,
+					x: goal_x,
+					y: goal_y,
+					cost: 0,
+				});
+				orders
+			},
+			None => make_path(obj, start.0, start.1, action_points, goal_x, goal_y, map_data),
+		};
+	}
+	
+	let mut orders = make_path(obj, start.0, start.1, action_points, goal_x, goal_y, map_data);
+	let last_pos = orders.last().map_or(start, |o| (o.x, o.y));
+	if orders_cost(&orders) < action_points && attack_order && tile_dist(map_data.topology, goal_x, goal_y, last_pos.0, last_pos.1) == 1
 	{
-		obj.orders.push(Order
+		orders.push(Order
 		{
 			order_type: OrderType::Attack,
 			x: goal_x,
 			y: goal_y,
+			// Attacking uses up all the remaining action points.
+			cost: 0,
 		});
 	}
+	orders
 }
 
-fn make_path(obj: &mut Object, goal_x: i32, goal_y: i32, map_data: &mut MapData)
+// The map as seen by a moving unit: it can walk through its allies, but has to stop on a free tile.
+struct UnitPassability<'l>
 {
-	obj.orders.clear();
-	
-	#[derive(Ord, PartialOrd, PartialEq, Eq)]
-	struct Node
+	map_data: &'l MapData,
+	is_ours: bool,
+	// Where the unit is standing, which it can always come back to.
+	home: (i32, i32),
+	zone_of_control: ZoneOfControl,
+}
+
+impl<'l> UnitPassability<'l>
+{
+	fn new(obj: &Object, map_data: &'l MapData) -> UnitPassability<'l>
+	{
+		UnitPassability
+		{
+			map_data,
+			is_ours: obj.is_ours,
+			home: (obj.tile_x, obj.tile_y),
+			zone_of_control: if obj.ignores_zone_of_control { ZoneOfControl::Off } else { map_data.zone_of_control },
+		}
+	}
+}
+
+impl<'l> Passability for UnitPassability<'l>
+{
+	fn can_step(&self, x: i32, y: i32, dx: i32, dy: i32) -> bool
 	{
-		// Since this is a max heap, we'll store negative scores.
-		neg_cost: i32,
-		x: i32,
-		y: i32,
+		// No cutting corners around water.
+		if is_diagonal_step(self.map_data.topology, dx, dy) &&
+			(map_terrain_solid(x + dx, y, self.map_data) || map_terrain_solid(x, y + dy, self.map_data))
+		{
+			return false;
+		}
+		map_tile_passable(x + dx, y + dy, self.is_ours, self.map_data)
 	}
-	#[derive(Ord, PartialOrd, PartialEq, Eq)]
-	struct ApproxNode
+
+	fn can_stop(&self, x: i32, y: i32) -> bool
 	{
-		// Since this is a max heap, we'll store negative scores.
-		neg_approx_cost: i32,
-		neg_true_cost: i32,
-		x: i32,
-		y: i32,
+		(x, y) == self.home || !map_tile_solid(x, y, self.map_data)
 	}
-	
-	let mut open_set = BinaryHeap::new();
-	let mut cost_map = HashMap::new();
-	let mut approx_cost_heap = BinaryHeap::new();
-	let mut came_from = HashMap::new();
-	open_set.push(Node
-	{
-		neg_cost: 0,
-		x: obj.tile_x,
-		y: obj.tile_y,
-	});
-	approx_cost_heap.push(ApproxNode
-	{
-		neg_approx_cost: -l1_dist(obj.tile_x, obj.tile_y, goal_x, goal_y),
-		neg_true_cost: 0,
-		x: obj.tile_x,
-		y: obj.tile_y,
-	});
-	cost_map.insert((obj.tile_x, obj.tile_y), 0);
-	
-	info!("Searching for {} {} from {} {}", goal_x, goal_y, obj.tile_x, obj.tile_y);
-	let mut found = None;
-	while !open_set.is_empty()
+
+	fn ends_move(&self, x: i32, y: i32) -> bool
 	{
-		let best = open_set.pop().unwrap();
-		let cost = *cost_map.get(&(best.x, best.y)).unwrap();
-		if best.x == goal_x && best.y == goal_y
+		if self.zone_of_control == ZoneOfControl::Off
 		{
-			found = Some((best.x, best.y));
-			break;
+			return false;
 		}
-		for m in &[(-1, 0), (1, 0), (0, -1), (0, 1)]
+		let map_data = self.map_data;
+		neighbour_offsets(map_data.topology, y).iter().any(|&(dx, dy)|
 		{
-			let cand_x = best.x + m.0;
-			let cand_y = best.y + m.1;
-			let cand_cost: i32 = cost + 1;
-			info!("trying: {} {} at {}", cand_x, cand_y, cand_cost);
-			if cost_map.get(&(cand_x, cand_y)).map_or(true, |&v| cand_cost < v) &&
-				!map_tile_solid(cand_x, cand_y, &*map_data) && cand_cost <= obj.action_points
-			{
-				let approx_cost = l1_dist(goal_x, goal_y, cand_x, cand_y);
-				open_set.push(Node
-				{
-					neg_cost: -(cand_cost + approx_cost),
-					x: cand_x,
-					y: cand_y,
-				});
-				approx_cost_heap.push(ApproxNode
-				{
-					neg_approx_cost: -approx_cost,
-					neg_true_cost: -cand_cost,
-					x: cand_x,
-					y: cand_y,
-				});
-				cost_map.insert((cand_x, cand_y), cand_cost);
-				came_from.insert((cand_x, cand_y), (best.x, best.y));
-			}
-		}
+			let (nx, ny) = (x + dx, y + dy);
+			nx >= 0 && ny >= 0 && nx < map_data.width && ny < map_data.height &&
+				map_data.collision_map[xy_to_index(nx, ny, map_data.width, map_data.height)] == Some(!self.is_ours)
+		})
 	}
+}
 
-	let mut cur;
-	match found
+fn get_grid(map_data: &MapData) -> Grid
+{
+	Grid
 	{
-		Some(found) => cur = found,
-		None =>
+		width: map_data.width,
+		height: map_data.height,
+		topology: map_data.topology,
+		diagonal_cost: map_data.diagonal_cost,
+	}
+}
+
+// Every tile the unit can move to with its current action points, with what it costs to get there.
+pub fn reachable_tiles(obj: &Object, map_data: &MapData) -> HashMap<(i32, i32), i32>
+{
+	unit_reachable_tiles(obj, (obj.tile_x, obj.tile_y), obj.action_points, map_data).into_iter()
+		.map(|(x, y, cost)| ((x, y), cost)).collect()
+}
+
+fn unit_reachable_tiles(obj: &Object, start: (i32, i32), action_points: i32, map_data: &MapData) -> Vec<(i32, i32, i32)>
+{
+	let mut tiles = vec![];
+	let passability = UnitPassability::new(obj, map_data);
+	map_data.pathfinder.borrow_mut().reachable(&get_grid(map_data), start, action_points, &passability, &mut tiles);
+	for tile in &mut tiles
+	{
+		if passability.zone_of_control == ZoneOfControl::Drain && (tile.0, tile.1) != start && passability.ends_move(tile.0, tile.1)
 		{
-			if let Some(approx_best) = approx_cost_heap.pop()
-			{
-				cur = (approx_best.x, approx_best.y);
-				info!("Got {:?}", cur);
-			}
-			else
-			{
-				info!("Got nothing");
-				return;
-			}
+			tile.2 = action_points;
 		}
 	}
-	loop
+	tiles
+}
+
+// Whether the unit could attack the target from x, y.
+pub fn in_attack_range(obj: &Object, x: i32, y: i32, target_x: i32, target_y: i32, map_data: &MapData) -> bool
+{
+	let dist = tile_dist(map_data.topology, x, y, target_x, target_y);
+	dist >= obj.min_range && dist <= obj.max_range && line_of_sight(x, y, target_x, target_y, map_data)
+}
+
+// Tiles of the enemies the unit could attack this turn, given its reachable tiles.
+pub fn attackable_tiles(obj: &Object, reachable: &HashMap<(i32, i32), i32>, objects: &IdMap<Object>, map_data: &MapData) -> Vec<(i32, i32)>
+{
+	let mut ret = vec![];
+	for enemy in objects.elems()
 	{
-		if cur.0 == obj.tile_x && cur.1 == obj.tile_y
+		if !enemy.has_health || !enemy.has_pos || enemy.is_ours == obj.is_ours
 		{
-			break;
+			continue;
+		}
+		// Attacking needs at least one action point left over.
+		if reachable.iter().any(|(&(x, y), &cost)| cost < obj.action_points &&
+			in_attack_range(obj, x, y, enemy.tile_x, enemy.tile_y, map_data))
+		{
+			ret.push((enemy.tile_x, enemy.tile_y));
 		}
-		obj.orders.push(Order{ x: cur.0, y: cur.1, order_type: OrderType::MoveTo });
-		cur = *came_from.get(&cur).unwrap();
 	}
-	obj.orders.reverse();
-	for &o in &obj.orders
+	ret
+}
+
+fn make_path(obj: &Object, start_x: i32, start_y: i32, action_points: i32, goal_x: i32, goal_y: i32, map_data: &MapData) -> Vec<Order>
+{
+	let mut path = vec![];
+	let passability = UnitPassability::new(obj, map_data);
+	let found = map_data.pathfinder.borrow_mut().find_path(&get_grid(map_data), (start_x, start_y), (goal_x, goal_y), action_points,
+		&passability, &mut path);
+	debug!("Path from {} {} to {} {}: {} steps, found: {}", start_x, start_y, goal_x, goal_y, path.len(), found);
+	
+	let mut prev = (start_x, start_y);
+	let mut orders = Vec::with_capacity(path.len());
+	for cur in path
 	{
-		info!("{:?}", o);
+		let cost = step_cost(map_data.topology, map_data.diagonal_cost, cur.0 - prev.0, cur.1 - prev.1);
+		orders.push(Order{ x: cur.0, y: cur.1, order_type: OrderType::MoveTo, cost });
+		prev = cur;
 	}
-	
-	info!("Done: {}", obj.orders.len());
+	if passability.zone_of_control == ZoneOfControl::Drain && passability.ends_move(prev.0, prev.1) && !orders.is_empty()
+	{
+		let spent = orders_cost(&orders[..orders.len() - 1]);
+		orders.last_mut().unwrap().cost = action_points - spent;
+	}
+	orders
 }
 
 complex_behavior!
@@ -172,7 +447,7 @@ complex_behavior!
 			{
 				return;
 			}
-			let mut executing_orders = None;
+			let mut executing_orders = false;
 			let mut adding_orders = None;
 			for obj in objects.elems_mut()
 			{
@@ -186,29 +461,31 @@ complex_behavior!
 					if last_order.x == state.cursor_x && last_order.y == state.cursor_y
 					{
 						obj.executing_orders = true;
-						executing_orders = Some(obj.get_id())
+						executing_orders = true;
 					}
 				}
-				if executing_orders.is_none() && (obj.tile_x != state.cursor_x || obj.tile_y != state.cursor_y)
+				if !executing_orders && (obj.tile_x != state.cursor_x || obj.tile_y != state.cursor_y)
 				{
 					adding_orders = Some(obj.get_id());
 				}
 				break;
 			}
-			if let Some(orders_id) = executing_orders
+			// The other units keep their orders, for Go to run later.
+			if executing_orders
 			{
+				save_undo(objects, &mut map_data);
 				map_data.executing_orders = true;
-				for obj in objects.elems_mut()
-				{
-					if obj.get_id() != orders_id
-					{
-						obj.orders.clear();
-					}
-				}
 			}
 			else if let Some(orders_id) = adding_orders
 			{
-				order_unit(orders_id, objects, state.cursor_x, state.cursor_y, &mut *map_data);
+				if state.shift_held
+				{
+					add_waypoint(orders_id, objects, state.cursor_x, state.cursor_y, &mut map_data);
+				}
+				else
+				{
+					order_unit(orders_id, objects, state.cursor_x, state.cursor_y, &mut map_data);
+				}
 			}
 		}
 	}
@@ -224,7 +501,11 @@ complex_behavior!
 		if let Some(ref map_data) = map_data
 		{
 			let mut map_data = map_data.borrow_mut();
-			let mut damage_this = None;
+			let mut attacks = vec![];
+			let mut ability_uses = vec![];
+			let mut cut_short = vec![];
+			// The attack isn't over until it lands.
+			let projectile_in_flight = objects.elems().iter().any(|obj| obj.is_projectile);
 			for obj in objects.elems_mut()
 			{
 				if !self.check_object(obj) || !obj.executing_orders
@@ -233,56 +514,70 @@ complex_behavior!
 				}
 				if let Some(&order) = obj.orders.first()
 				{
-					let dest_x = order.x as f32 * 32.0;
-					let dest_y = order.y as f32 * 32.0;
+					let (dest_x, dest_y) = tile_to_world(map_data.topology, order.x, order.y);
 					match order.order_type
 					{
 						OrderType::MoveTo =>
 						{
+							// Average speed, in pixels per second.
 							const SPEED: f32 = 256.0;
-							if (obj.x - dest_x).abs() < SPEED * DT * 1.1 && (obj.y - dest_y).abs() < SPEED * DT * 1.1
-							{
-								obj.x = dest_x;
-								obj.y = dest_y;
-								obj.orders.remove(0);
-								obj.action_points -= 1;
-							}
-							if obj.x < dest_x
-							{
-								obj.x += SPEED * DT;
-							}
-							if obj.x > dest_x
-							{
-								obj.x -= SPEED * DT;
-							}
-							if obj.y < dest_y
+							if obj.step_progress == 0.0
 							{
-								obj.y += SPEED * DT;
+								if (order.x, order.y) != (obj.tile_x, obj.tile_y)
+								{
+									// Another unit may have broken the ice on the way since this was planned.
+									if map_terrain_solid(order.x, order.y, &map_data)
+									{
+										clear_orders(obj, map_data.topology);
+										cut_short.push(obj.get_id());
+										continue;
+									}
+									// Thin ice gives way as soon as the unit steps off it.
+									break_ice(obj.tile_x, obj.tile_y, &mut map_data);
+								}
+								obj.step_from_x = obj.x;
+								obj.step_from_y = obj.y;
+								face_towards(obj, order.x);
 							}
-							if obj.y > dest_y
+							let step_len = ((dest_x - obj.step_from_x).powi(2) + (dest_y - obj.step_from_y).powi(2)).sqrt();
+							obj.step_progress = if step_len > 0.0 { (obj.step_progress + SPEED * DT / step_len).min(1.0) } else { 1.0 };
+							// Speeds up out of each tile and slows down into the next.
+							let t = obj.step_progress;
+							let f = t * t * (3.0 - 2.0 * t);
+							obj.x = obj.step_from_x + (dest_x - obj.step_from_x) * f;
+							obj.y = obj.step_from_y + (dest_y - obj.step_from_y) * f;
+							if obj.step_progress >= 1.0
 							{
-								obj.y -= SPEED * DT;
+								obj.step_progress = 0.0;
+								obj.orders.remove(0);
+								obj.action_points -= order.cost;
 							}
 						},
 						OrderType::Attack =>
 						{
-							if obj.fire
-							{
-								let slash = create_fire(state.current_map_id, dest_x, dest_y, state);
-								state.add_object(slash);
-							}
-							else
-							{
-								let slash = create_slash(state.current_map_id, dest_x, dest_y, state);
-								state.add_object(slash);
-							}
+							face_towards(obj, order.x);
 							obj.orders.remove(0);
 							obj.action_points = 0;
-							damage_this = Some((order.x, order.y, -obj.damage));
-						}
+							attacks.push(Attack::new(obj, order.x, order.y));
+						},
+						OrderType::RangedAttack =>
+						{
+							face_towards(obj, order.x);
+							let projectile = create_projectile(state.current_map_id, obj, order.x, order.y, state);
+							state.add_object(projectile);
+							obj.orders.remove(0);
+							obj.action_points = 0;
+						},
+						OrderType::Ability(idx) =>
+						{
+							// Paid for by use_ability, once it's clear it still works.
+							face_towards(obj, order.x);
+							obj.orders.remove(0);
+							ability_uses.push((obj.get_id(), idx, order.x, order.y));
+						},
 					}
 				}
-				else
+				else if !projectile_in_flight
 				{
 					obj.executing_orders = false;
 					if !obj.is_ours
@@ -290,55 +585,361 @@ complex_behavior!
 						// HACK!
 						obj.action_points = 0;
 					}
-					map_data.executing_orders = false;
 				}
 			}
-			if let Some((x, y, change)) = damage_this
+			for attack in attacks
+			{
+				resolve_attack(attack, objects, &mut map_data, state);
+			}
+			for id in cut_short
+			{
+				let obj = objects.get(id).unwrap();
+				let on_ally = objects.elems().iter().any(|other| other.get_id() != id && other.is_solid && other.has_pos &&
+					(other.tile_x, other.tile_y) == (obj.tile_x, obj.tile_y));
+				if on_ally
+				{
+					let orders = step_off_orders(obj, &map_data);
+					replace_orders(objects.get_mut(id).unwrap(), orders, map_data.topology);
+				}
+			}
+			for (user_id, idx, x, y) in ability_uses
+			{
+				use_ability(user_id, idx, x, y, objects, &mut map_data, state);
+			}
+			if !projectile_in_flight && !objects.elems().iter().any(|obj| obj.executing_orders)
 			{
-				for obj in objects.elems_mut()
+				if map_data.order_queue.is_empty()
 				{
-					if obj.has_health && obj.tile_x == x && obj.tile_y == y
+					map_data.executing_orders = false;
+				}
+				else
+				{
+					let next_id = map_data.order_queue.remove(0);
+					if let Some(obj) = objects.get_mut(next_id)
 					{
-						obj.health += change;
+						obj.executing_orders = true;
 					}
 				}
 			}
+			map_data.num_planned = objects.elems().iter().filter(|obj| obj.is_ours && obj.can_act && obj.action_points > 0 &&
+				!obj.orders.is_empty() && !obj.executing_orders).count();
+		}
+	}
+}
+
+// A way off the tile of an ally the unit was only passing through, to the nearest one it can stop
+// on. The unit already paid to get this far, so it doesn't pay for this.
+fn step_off_orders(obj: &Object, map_data: &MapData) -> Vec<Order>
+{
+	let start = (obj.tile_x, obj.tile_y);
+	let spot = unit_reachable_tiles(obj, start, obj.max_action_points, map_data).into_iter()
+		.filter(|&(x, y, _)| (x, y) != start)
+		.min_by_key(|t| t.2);
+	match spot
+	{
+		Some((x, y, _)) =>
+		{
+			let mut orders = make_path(obj, start.0, start.1, obj.max_action_points, x, y, map_data);
+			for order in &mut orders
+			{
+				order.cost = 0;
+			}
+			orders
+		},
+		None =>
+		{
+			warn!("{} is stuck on an ally's tile at {} {}", obj.name, start.0, start.1);
+			vec![]
+		},
+	}
+}
+
+fn face_towards(obj: &mut Object, tile_x: i32)
+{
+	if tile_x != obj.tile_x
+	{
+		obj.facing_right = tile_x > obj.tile_x;
+	}
+}
+
+// Shows the hit and damages whatever is on the tile. A defender that survives strikes back if it
+// can reach the attacker.
+pub fn resolve_attack(attack: Attack, objects: &mut IdMap<Object>, map_data: &mut MapData, state: &mut GameState)
+{
+	let Attack { x, y, damage_type, .. } = attack;
+	let (effect_x, effect_y) = tile_to_world(map_data.topology, x, y);
+	let fire = damage_type == DamageType::Fire;
+	let effect = if fire
+	{
+		create_fire(state.current_map_id, effect_x, effect_y, state)
+	}
+	else
+	{
+		create_slash(state.current_map_id, effect_x, effect_y, state)
+	};
+	state.add_object(effect);
+	forget_undo(map_data);
+	if fire
+	{
+		melt_tile(x, y, map_data);
+	}
+	let mut kills = 0;
+	for obj in objects.elems_mut()
+	{
+		if obj.has_health && obj.tile_x == x && obj.tile_y == y
+		{
+			let damage = match roll_attack(&attack, obj.evasion, map_data.deterministic, &mut state.rng)
+			{
+				Roll::Miss =>
+				{
+					let announce = create_announcement(state.current_map_id, "Miss!", state);
+					state.add_object(announce);
+					continue;
+				},
+				Roll::Hit(damage) => damage,
+				Roll::Critical(damage) =>
+				{
+					let announce = create_announcement(state.current_map_id, "Critical hit!", state);
+					state.add_object(announce);
+					damage
+				},
+			};
+			let mut taken = damage_taken(damage, damage_type, obj.armour, &obj.resistances);
+			if has_status(obj, StatusKind::Shielded)
+			{
+				taken /= 2;
+			}
+			debug!("{} takes {} of {} {:?} damage", obj.name, taken, damage, damage_type);
+			if obj.can_act && obj.health > 0 && obj.health <= taken
+			{
+				kills += 1;
+			}
+			obj.health -= taken;
+			match damage_type
+			{
+				DamageType::Fire if obj.resistances.fire < 100 =>
+				{
+					remove_status(obj, StatusKind::Frozen);
+					add_status(obj, StatusKind::Burning, 2);
+				},
+				DamageType::Frost if obj.resistances.frost < 100 =>
+				{
+					remove_status(obj, StatusKind::Burning);
+					add_status(obj, StatusKind::Frozen, 1);
+				},
+				_ => (),
+			}
+		}
+	}
+
+	if kills > 0
+	{
+		if let Some(source) = objects.get_mut(attack.source_id).filter(|obj| obj.health > 0)
+		{
+			gain_experience(source, KILL_EXPERIENCE * kills, state);
+		}
+	}
+
+	let attacker = match attack.attacker_id.and_then(|id| objects.get(id))
+	{
+		Some(attacker) if attacker.health > 0 => attacker,
+		_ => return,
+	};
+	let (attacker_x, attacker_y) = (attacker.tile_x, attacker.tile_y);
+	let defender = objects.elems_mut().iter_mut().find(|obj| obj.can_act && obj.has_health && obj.tile_x == x && obj.tile_y == y);
+	if let Some(defender) = defender
+	{
+		let damage = defender.damage * defender.retaliation / 100;
+		if defender.health <= 0 || damage <= 0 || !in_attack_range(defender, x, y, attacker_x, attacker_y, map_data)
+		{
+			return;
 		}
+		info!("{} strikes back", defender.name);
+		face_towards(defender, attacker_x);
+		let mut counter = Attack::new(defender, attacker_x, attacker_y);
+		counter.damage = damage;
+		// No striking back at a counterattack.
+		counter.attacker_id = None;
+		resolve_attack(counter, objects, map_data, state);
 	}
 }
 
-simple_behavior!
+fn draw_orders(orders: &[Order], skip_first: bool, topology: Topology, state: &GameState)
 {
-	PathableDraw[obj.has_pos && obj.can_act && obj.is_selectable] |obj, state|
+	let path_len = orders.len();
+	for (count, &order) in orders.iter().enumerate()
 	{
-		if !obj.selected
+		if skip_first && count == 0
 		{
 			continue;
 		}
-		let path_len = obj.orders.len();
-		for (count, &order) in obj.orders.iter().enumerate()
+		let ani = match order.order_type
 		{
-			if obj.executing_orders && count == 0
+			OrderType::MoveTo =>
+				if count == path_len - 1
+				{
+					state.path_end.as_ref().unwrap()
+				}
+				else
+				{
+					state.path_marker.as_ref().unwrap()
+				},
+			OrderType::Attack | OrderType::RangedAttack | OrderType::Ability(_) =>
 			{
-				continue;
+				state.path_attack.as_ref().unwrap()
+			}
+		};
+		let (x, y) = tile_to_world(topology, order.x, order.y);
+		ani.draw(x - 16.0, y - 16.0, state);
+	}
+}
+
+pub struct PathableDraw;
+
+complex_behavior!
+{
+	PathableDraw[obj.has_pos && obj.can_act && obj.is_selectable] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let map_data = map_data.borrow();
+			for obj in objects.elems()
+			{
+				if !self.check_object(obj) || (!obj.selected && !obj.is_ours)
+				{
+					continue;
+				}
+				draw_orders(&obj.orders, obj.executing_orders, map_data.topology, state);
+			}
+		}
+	}
+}
+
+pub struct PathPreviewLogic;
+
+complex_behavior!
+{
+	PathPreviewLogic[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let mut map_data = map_data.borrow_mut();
+			let unit = objects.elems().iter().find(|obj| obj.selected && obj.is_ours && obj.can_act && obj.has_pos && obj.action_points > 0);
+			let unit = match unit
+			{
+				Some(unit) if map_data.our_turn && !map_data.executing_orders && !map_data.crafting => unit,
+				_ =>
+				{
+					map_data.path_preview = None;
+					return;
+				}
+			};
+			// The path that's already been ordered is shown instead.
+			let at_path_end = unit.orders.last().is_some_and(|o| o.x == state.cursor_x && o.y == state.cursor_y);
+			if at_path_end || (unit.tile_x == state.cursor_x && unit.tile_y == state.cursor_y)
+			{
+				map_data.path_preview = None;
+				return;
+			}
+			let up_to_date = map_data.path_preview.as_ref().is_some_and(|p| p.unit_id == unit.get_id() &&
+				p.tile_x == unit.tile_x && p.tile_y == unit.tile_y && p.action_points == unit.action_points &&
+				p.cursor_x == state.cursor_x && p.cursor_y == state.cursor_y && p.num_orders == unit.orders.len() &&
+				p.adding_waypoint == state.shift_held && p.ability == unit.active_ability && p.generation == map_data.generation);
+			if !up_to_date
+			{
+				let orders = if state.shift_held
+				{
+					plan_waypoint_orders(unit.get_id(), objects, state.cursor_x, state.cursor_y, &map_data)
+				}
+				else
+				{
+					plan_orders(unit.get_id(), objects, state.cursor_x, state.cursor_y, &map_data)
+				};
+				map_data.path_preview = Some(PathPreview
+				{
+					unit_id: unit.get_id(),
+					tile_x: unit.tile_x,
+					tile_y: unit.tile_y,
+					action_points: unit.action_points,
+					cursor_x: state.cursor_x,
+					cursor_y: state.cursor_y,
+					num_orders: unit.orders.len(),
+					adding_waypoint: state.shift_held,
+					ability: unit.active_ability,
+					generation: map_data.generation,
+					orders,
+				});
 			}
-			let ani = match order.order_type
+		}
+	}
+}
+
+pub struct PathPreviewDraw;
+
+complex_behavior!
+{
+	PathPreviewDraw[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let map_data = map_data.borrow();
+			if let Some(ref preview) = map_data.path_preview
 			{
-				OrderType::MoveTo =>
-					if count == path_len - 1
+				if let Some(last) = preview.orders.last()
+				{
+					draw_orders(&preview.orders, false, map_data.topology, state);
+					let mut text = format!("{} AP", orders_cost(&preview.orders));
+					if last.order_type.is_attack()
 					{
-						state.path_end.as_ref().unwrap()
+						text.push_str(", attack!");
 					}
-					else
+					else if let OrderType::Ability(idx) = last.order_type
 					{
-						state.path_marker.as_ref().unwrap()
-					},
-				OrderType::Attack =>
+						if let Some(ability) = objects.get(preview.unit_id).and_then(|unit| unit.abilities.get(idx))
+						{
+							text.push_str(&format!(", {}!", ability.name()));
+						}
+					}
+					let (x, y) = tile_to_world(map_data.topology, last.x, last.y);
+					state.core.draw_text(&state.ui_font, Color::from_rgba(224, 224, 224, 255), x, y - 28.0, FontAlign::Centre, &text);
+				}
+			}
+		}
+	}
+}
+
+pub struct ReachableDraw;
+
+complex_behavior!
+{
+	ReachableDraw[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let map_data = map_data.borrow();
+			if !map_data.our_turn || map_data.executing_orders || map_data.crafting
+			{
+				return;
+			}
+			let unit = objects.elems().iter().find(|obj| obj.selected && obj.is_ours && obj.can_act && obj.has_pos && obj.action_points > 0);
+			if let Some(unit) = unit
+			{
+				let reachable = reachable_tiles(unit, &map_data);
+				for &(x, y) in reachable.keys()
 				{
-					state.path_attack.as_ref().unwrap()
+					let (x, y) = tile_to_world(map_data.topology, x, y);
+					state.prim.draw_filled_rectangle(x - 15.0, y - 15.0, x + 15.0, y + 15.0, Color::from_rgba(32, 64, 128, 64));
 				}
-			};
-			ani.draw(order.x as f32 * 32.0 - 16.0, order.y as f32 * 32.0 - 16.0, state);
+				for (x, y) in attackable_tiles(unit, &reachable, objects, &map_data)
+				{
+					let (x, y) = tile_to_world(map_data.topology, x, y);
+					state.prim.draw_rectangle(x - 14.0, y - 14.0, x + 14.0, y + 14.0, Color::from_rgb(255, 64, 64), 2.0);
+				}
+			}
 		}
 	}
 }
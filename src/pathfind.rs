@@ -0,0 +1,352 @@
+// Grid pathfinding that reuses its buffers between searches.
+
+use std::collections::BinaryHeap;
+use topology::{Topology, neighbour_offsets, step_cost, cost_estimate};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Grid
+{
+	pub width: i32,
+	pub height: i32,
+	pub topology: Topology,
+	pub diagonal_cost: i32,
+}
+
+pub trait Passability
+{
+	// Whether a unit could step from x, y by dx, dy.
+	fn can_step(&self, x: i32, y: i32, dx: i32, dy: i32) -> bool;
+
+	// Whether a unit could end its move on x, y. Tiles it can only pass through return false.
+	fn can_stop(&self, _x: i32, _y: i32) -> bool
+	{
+		true
+	}
+
+	// Whether entering x, y ends the move, e.g. because of an enemy's zone of control.
+	fn ends_move(&self, _x: i32, _y: i32) -> bool
+	{
+		false
+	}
+}
+
+impl<F> Passability for F where F: Fn(i32, i32, i32, i32) -> bool
+{
+	fn can_step(&self, x: i32, y: i32, dx: i32, dy: i32) -> bool
+	{
+		self(x, y, dx, dy)
+	}
+}
+
+#[derive(Ord, PartialOrd, PartialEq, Eq)]
+struct Node
+{
+	// Since this is a max heap, we'll store negative scores.
+	neg_estimate: i32,
+	// Among equal estimates, prefer the nodes further along.
+	cost: i32,
+	idx: u32,
+}
+
+pub struct Pathfinder
+{
+	width: i32,
+	cost: Vec<i32>,
+	came_from: Vec<u32>,
+	// A tile's cost and came_from are only valid when its stamp is the current generation, so
+	// nothing needs clearing between searches.
+	seen: Vec<u32>,
+	closed: Vec<u32>,
+	generation: u32,
+	open_set: BinaryHeap<Node>,
+}
+
+impl Pathfinder
+{
+	pub fn new() -> Pathfinder
+	{
+		Pathfinder
+		{
+			width: 0,
+			cost: vec![],
+			came_from: vec![],
+			seen: vec![],
+			closed: vec![],
+			generation: 0,
+			open_set: BinaryHeap::new(),
+		}
+	}
+
+	fn begin(&mut self, grid: &Grid)
+	{
+		let size = (grid.width * grid.height) as usize;
+		if self.width != grid.width || self.seen.len() != size
+		{
+			self.width = grid.width;
+			self.cost.clear();
+			self.cost.resize(size, 0);
+			self.came_from.clear();
+			self.came_from.resize(size, 0);
+			self.seen.clear();
+			self.seen.resize(size, 0);
+			self.closed.clear();
+			self.closed.resize(size, 0);
+		}
+		self.generation = self.generation.wrapping_add(1);
+		if self.generation == 0
+		{
+			for v in &mut self.seen
+			{
+				*v = 0;
+			}
+			for v in &mut self.closed
+			{
+				*v = 0;
+			}
+			self.generation = 1;
+		}
+		self.open_set.clear();
+	}
+
+	fn xy(&self, idx: u32) -> (i32, i32)
+	{
+		(idx as i32 % self.width, idx as i32 / self.width)
+	}
+
+	fn open(&mut self, idx: usize, cost: i32, came_from: u32, estimate: i32)
+	{
+		self.seen[idx] = self.generation;
+		self.cost[idx] = cost;
+		self.came_from[idx] = came_from;
+		self.open_set.push(Node
+		{
+			neg_estimate: -estimate,
+			cost,
+			idx: idx as u32,
+		});
+	}
+
+	// Searches the grid with A*, visiting every tile within max_cost of the start. The steps after the
+	// start are written into path. If the goal can't be reached, the path leads to the closest tile to it
+	// that the unit can stop on instead, and false is returned.
+	pub fn find_path<P>(&mut self, grid: &Grid, start: (i32, i32), goal: (i32, i32), max_cost: i32,
+		passability: &P, path: &mut Vec<(i32, i32)>) -> bool
+		where P: Passability
+	{
+		path.clear();
+		if start.0 < 0 || start.1 < 0 || start.0 >= grid.width || start.1 >= grid.height
+		{
+			return false;
+		}
+		self.begin(grid);
+		let estimate = |x, y| cost_estimate(grid.topology, grid.diagonal_cost, x, y, goal.0, goal.1);
+		let start_idx = (start.1 * grid.width + start.0) as usize;
+		self.open(start_idx, 0, start_idx as u32, estimate(start.0, start.1));
+
+		let mut found = false;
+		// The closest tile to the goal so far, by the estimate and then the cost.
+		let mut best = (estimate(start.0, start.1), 0, start_idx as u32);
+		while let Some(node) = self.open_set.pop()
+		{
+			let idx = node.idx as usize;
+			if self.closed[idx] == self.generation
+			{
+				continue;
+			}
+			self.closed[idx] = self.generation;
+			let (x, y) = self.xy(node.idx);
+			let can_stop = idx == start_idx || passability.can_stop(x, y);
+			let node_estimate = estimate(x, y);
+			if can_stop && (node_estimate, node.cost) < (best.0, best.1)
+			{
+				best = (node_estimate, node.cost, node.idx);
+			}
+			if can_stop && (x, y) == goal
+			{
+				found = true;
+				break;
+			}
+			if idx != start_idx && passability.ends_move(x, y)
+			{
+				continue;
+			}
+			for &(dx, dy) in neighbour_offsets(grid.topology, y)
+			{
+				let (cand_x, cand_y) = (x + dx, y + dy);
+				if cand_x < 0 || cand_y < 0 || cand_x >= grid.width || cand_y >= grid.height
+				{
+					continue;
+				}
+				let cand_idx = (cand_y * grid.width + cand_x) as usize;
+				let cand_cost = node.cost + step_cost(grid.topology, grid.diagonal_cost, dx, dy);
+				if cand_cost > max_cost || self.closed[cand_idx] == self.generation ||
+					(self.seen[cand_idx] == self.generation && self.cost[cand_idx] <= cand_cost) || !passability.can_step(x, y, dx, dy)
+				{
+					continue;
+				}
+				self.open(cand_idx, cand_cost, node.idx, cand_cost + estimate(cand_x, cand_y));
+			}
+		}
+
+		let mut cur = best.2;
+		while cur as usize != start_idx
+		{
+			path.push(self.xy(cur));
+			cur = self.came_from[cur as usize];
+		}
+		path.reverse();
+		found
+	}
+
+	// Every tile within max_cost of the start that the unit can stop on, with what it costs to get there.
+	pub fn reachable<P>(&mut self, grid: &Grid, start: (i32, i32), max_cost: i32, passability: &P, tiles: &mut Vec<(i32, i32, i32)>)
+		where P: Passability
+	{
+		tiles.clear();
+		if start.0 < 0 || start.1 < 0 || start.0 >= grid.width || start.1 >= grid.height
+		{
+			return;
+		}
+		self.begin(grid);
+		let start_idx = (start.1 * grid.width + start.0) as usize;
+		self.open(start_idx, 0, start_idx as u32, 0);
+		while let Some(node) = self.open_set.pop()
+		{
+			let idx = node.idx as usize;
+			if self.closed[idx] == self.generation
+			{
+				continue;
+			}
+			self.closed[idx] = self.generation;
+			let (x, y) = self.xy(node.idx);
+			if idx == start_idx || passability.can_stop(x, y)
+			{
+				tiles.push((x, y, node.cost));
+			}
+			if idx != start_idx && passability.ends_move(x, y)
+			{
+				continue;
+			}
+			for &(dx, dy) in neighbour_offsets(grid.topology, y)
+			{
+				let (cand_x, cand_y) = (x + dx, y + dy);
+				if cand_x < 0 || cand_y < 0 || cand_x >= grid.width || cand_y >= grid.height
+				{
+					continue;
+				}
+				let cand_idx = (cand_y * grid.width + cand_x) as usize;
+				let cand_cost = node.cost + step_cost(grid.topology, grid.diagonal_cost, dx, dy);
+				if cand_cost > max_cost || self.closed[cand_idx] == self.generation ||
+					(self.seen[cand_idx] == self.generation && self.cost[cand_idx] <= cand_cost) || !passability.can_step(x, y, dx, dy)
+				{
+					continue;
+				}
+				self.open(cand_idx, cand_cost, node.idx, cand_cost);
+			}
+		}
+	}
+}
+
+#[test]
+fn matches_flood_fill()
+{
+	// A maze-ish map with a wall every third column, each with a gap.
+	let grid = Grid
+	{
+		width: 30,
+		height: 20,
+		topology: Topology::Square,
+		diagonal_cost: 2,
+	};
+	let solid = |x: i32, y: i32| x % 3 == 2 && y != (x * 7) % grid.height;
+	let can_step = |x: i32, y: i32, dx: i32, dy: i32| !solid(x + dx, y + dy);
+
+	let mut pathfinder = Pathfinder::new();
+	let mut tiles = vec![];
+	pathfinder.reachable(&grid, (0, 0), 1000, &can_step, &mut tiles);
+	let mut path = vec![];
+	// The first tile is the start.
+	for &(x, y, cost) in tiles.iter().skip(1)
+	{
+		assert!(pathfinder.find_path(&grid, (0, 0), (x, y), 1000, &can_step, &mut path));
+		assert_eq!(cost as usize, path.len());
+		assert_eq!(Some(&(x, y)), path.last());
+	}
+	// Out of reach, so it stops at the closest tile instead.
+	assert!(!pathfinder.find_path(&grid, (0, 0), (1, 15), 10, &can_step, &mut path));
+	assert_eq!(10, path.len());
+	assert!(path.last().unwrap().1 >= 9);
+	assert!(!pathfinder.find_path(&grid, (0, 0), (2, 0), 1000, &can_step, &mut path));
+	assert_eq!(Some(&(1, 0)), path.last());
+}
+
+#[test]
+fn passes_through_allies()
+{
+	struct Corridor;
+	impl Passability for Corridor
+	{
+		fn can_step(&self, _x: i32, _y: i32, _dx: i32, _dy: i32) -> bool
+		{
+			true
+		}
+
+		fn can_stop(&self, x: i32, _y: i32) -> bool
+		{
+			x != 2
+		}
+	}
+	let grid = Grid
+	{
+		width: 5,
+		height: 1,
+		topology: Topology::Square,
+		diagonal_cost: 2,
+	};
+	let mut pathfinder = Pathfinder::new();
+	let mut path = vec![];
+	assert!(pathfinder.find_path(&grid, (0, 0), (4, 0), 10, &Corridor, &mut path));
+	assert_eq!(vec![(1, 0), (2, 0), (3, 0), (4, 0)], path);
+	assert!(!pathfinder.find_path(&grid, (0, 0), (2, 0), 10, &Corridor, &mut path));
+	assert_eq!(vec![(1, 0)], path);
+	assert!(!pathfinder.find_path(&grid, (0, 0), (4, 0), 2, &Corridor, &mut path));
+	assert_eq!(vec![(1, 0)], path);
+}
+
+#[test]
+fn stops_in_zone_of_control()
+{
+	// An enemy that controls the tiles around it.
+	struct Field(i32, i32);
+	impl Passability for Field
+	{
+		fn can_step(&self, x: i32, y: i32, dx: i32, dy: i32) -> bool
+		{
+			(x + dx, y + dy) != (self.0, self.1)
+		}
+
+		fn ends_move(&self, x: i32, y: i32) -> bool
+		{
+			(x - self.0).abs() + (y - self.1).abs() == 1
+		}
+	}
+	let mut grid = Grid
+	{
+		width: 5,
+		height: 5,
+		topology: Topology::Square,
+		diagonal_cost: 2,
+	};
+	let mut pathfinder = Pathfinder::new();
+	let mut path = vec![];
+	// Going straight past the enemy would end the move, so it goes around.
+	assert!(pathfinder.find_path(&grid, (0, 2), (4, 2), 20, &Field(2, 2), &mut path));
+	assert_eq!(8, path.len());
+
+	// Nothing past the enemy is reachable.
+	grid.height = 2;
+	let mut tiles = vec![];
+	pathfinder.reachable(&grid, (0, 1), 20, &Field(2, 0), &mut tiles);
+	assert_eq!(5, tiles.len());
+	assert!(tiles.iter().all(|t| t.0 <= 2));
+}
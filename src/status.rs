@@ -0,0 +1,142 @@
+use std::cmp::min;
+use allegro::*;
+use game_state::*;
+use combat::{DamageType, damage_taken};
+use effect::create_fire;
+use unit::draw_pos;
+
+const BURN_DAMAGE: i32 = 1;
+
+slr_def!
+{
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	pub enum StatusKind
+	{
+		// Takes fire damage every turn.
+		Burning,
+		// Skips its turns.
+		Frozen,
+		// Gets half its action points.
+		Slowed,
+		// Takes half damage.
+		Shielded,
+		// Deals half again as much damage.
+		Enchanted
+	}
+}
+
+slr_def!
+{
+	#[derive(Clone, Debug)]
+	pub struct Status
+	{
+		kind: StatusKind = StatusKind::Burning,
+		// How many more times the turns roll over before it wears off.
+		turns: i32 = 1
+	}
+}
+
+impl Default for Status
+{
+	fn default() -> Status
+	{
+		Status::new()
+	}
+}
+
+impl StatusKind
+{
+	pub fn name(&self) -> &'static str
+	{
+		match *self
+		{
+			StatusKind::Burning => "Burning",
+			StatusKind::Frozen => "Frozen",
+			StatusKind::Slowed => "Slowed",
+			StatusKind::Shielded => "Shielded",
+			StatusKind::Enchanted => "Enchanted",
+		}
+	}
+
+	pub fn color(&self) -> Color
+	{
+		match *self
+		{
+			StatusKind::Burning => Color::from_rgb(255, 128, 32),
+			StatusKind::Frozen => Color::from_rgb(160, 224, 255),
+			StatusKind::Slowed => Color::from_rgb(128, 128, 192),
+			StatusKind::Shielded => Color::from_rgb(255, 224, 64),
+			StatusKind::Enchanted => Color::from_rgb(224, 96, 255),
+		}
+	}
+}
+
+pub fn has_status(obj: &Object, kind: StatusKind) -> bool
+{
+	obj.statuses.iter().any(|s| s.kind == kind)
+}
+
+pub fn remove_status(obj: &mut Object, kind: StatusKind)
+{
+	obj.statuses.retain(|s| s.kind != kind);
+}
+
+// Gives the unit the status, or makes it last longer if it already has it.
+pub fn add_status(obj: &mut Object, kind: StatusKind, turns: i32)
+{
+	match obj.statuses.iter_mut().find(|s| s.kind == kind)
+	{
+		Some(status) => status.turns = status.turns.max(turns),
+		None => obj.statuses.push(Status { kind, turns }),
+	}
+	// These take effect right away, rather than on the unit's next turn.
+	match kind
+	{
+		StatusKind::Frozen => obj.action_points = 0,
+		StatusKind::Slowed => obj.action_points = min(obj.action_points, obj.max_action_points / 2),
+		_ => (),
+	}
+}
+
+// Called for every unit at the start of its side's phase, after its action points are restored.
+pub fn tick_statuses(obj: &mut Object, state: &mut GameState)
+{
+	if has_status(obj, StatusKind::Burning)
+	{
+		let mut damage = damage_taken(BURN_DAMAGE, DamageType::Fire, obj.armour, &obj.resistances);
+		if has_status(obj, StatusKind::Shielded)
+		{
+			damage /= 2;
+		}
+		obj.health -= damage;
+		let effect = create_fire(state.current_map_id, obj.x, obj.y, state);
+		state.add_object(effect);
+	}
+	if has_status(obj, StatusKind::Slowed)
+	{
+		obj.action_points = obj.max_action_points / 2;
+	}
+	if has_status(obj, StatusKind::Frozen)
+	{
+		obj.action_points = 0;
+	}
+	for status in &mut obj.statuses
+	{
+		status.turns -= 1;
+	}
+	obj.statuses.retain(|s| s.turns > 0);
+}
+
+simple_behavior!
+{
+	StatusDraw[obj.has_pos && obj.can_act && !obj.statuses.is_empty()] |obj, state|
+	{
+		// A row of pips along the top of the unit.
+		let (x, y) = draw_pos(obj, state);
+		for (i, status) in obj.statuses.iter().enumerate()
+		{
+			let pip_x = x - 14.0 + 6.0 * i as f32;
+			state.prim.draw_filled_rectangle(pip_x, y - 16.0, pip_x + 4.0, y - 12.0, status.kind.color());
+		}
+	}
+}
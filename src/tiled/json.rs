@@ -0,0 +1,245 @@
+// Just enough JSON to read Tiled's exports.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json
+{
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+}
+
+impl Json
+{
+	pub fn get(&self, key: &str) -> Option<&Json>
+	{
+		match *self
+		{
+			Json::Object(ref members) => members.iter().find(|m| m.0 == key).map(|m| &m.1),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> &[Json]
+	{
+		match *self
+		{
+			Json::Array(ref elems) => elems,
+			_ => &[],
+		}
+	}
+
+	pub fn as_f64(&self) -> Option<f64>
+	{
+		match *self
+		{
+			Json::Number(v) => Some(v),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str>
+	{
+		match *self
+		{
+			Json::String(ref v) => Some(v),
+			_ => None,
+		}
+	}
+
+	// Tiled stores property values as strings, numbers or booleans.
+	pub fn to_property_string(&self) -> String
+	{
+		match *self
+		{
+			Json::Null => String::new(),
+			Json::Bool(v) => v.to_string(),
+			Json::Number(v) => v.to_string(),
+			Json::String(ref v) => v.clone(),
+			Json::Array(_) | Json::Object(_) => String::new(),
+		}
+	}
+}
+
+struct Parser<'l>
+{
+	chars: &'l [char],
+	pos: usize,
+}
+
+impl<'l> Parser<'l>
+{
+	fn skip_whitespace(&mut self)
+	{
+		while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace()
+		{
+			self.pos += 1;
+		}
+	}
+
+	fn peek(&mut self) -> Option<char>
+	{
+		self.skip_whitespace();
+		self.chars.get(self.pos).cloned()
+	}
+
+	fn expect(&mut self, c: char) -> Result<(), String>
+	{
+		if self.peek() == Some(c)
+		{
+			self.pos += 1;
+			Ok(())
+		}
+		else
+		{
+			Err(format!("Expected '{}' at {}", c, self.pos))
+		}
+	}
+
+	fn literal(&mut self, lit: &str, val: Json) -> Result<Json, String>
+	{
+		for c in lit.chars()
+		{
+			if self.chars.get(self.pos) != Some(&c)
+			{
+				return Err(format!("Invalid literal at {}", self.pos));
+			}
+			self.pos += 1;
+		}
+		Ok(val)
+	}
+
+	fn string(&mut self) -> Result<String, String>
+	{
+		self.expect('"')?;
+		let mut ret = String::new();
+		loop
+		{
+			let c = *self.chars.get(self.pos).ok_or("Unterminated string")?;
+			self.pos += 1;
+			match c
+			{
+				'"' => return Ok(ret),
+				'\\' =>
+				{
+					let e = *self.chars.get(self.pos).ok_or("Unterminated string")?;
+					self.pos += 1;
+					match e
+					{
+						'n' => ret.push('\n'),
+						't' => ret.push('\t'),
+						'r' => ret.push('\r'),
+						'b' => ret.push('\u{8}'),
+						'f' => ret.push('\u{c}'),
+						'u' =>
+						{
+							let hex: String = self.chars.iter().skip(self.pos).take(4).collect();
+							self.pos += 4;
+							let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("Invalid escape at {}", self.pos))?;
+							ret.push(::std::char::from_u32(code).unwrap_or('?'));
+						},
+						other => ret.push(other),
+					}
+				},
+				other => ret.push(other),
+			}
+		}
+	}
+
+	fn number(&mut self) -> Result<Json, String>
+	{
+		let start = self.pos;
+		while self.pos < self.chars.len() && "+-0123456789.eE".contains(self.chars[self.pos])
+		{
+			self.pos += 1;
+		}
+		let text: String = self.chars[start..self.pos].iter().collect();
+		text.parse().map(Json::Number).map_err(|_| format!("Invalid number '{}' at {}", text, start))
+	}
+
+	fn value(&mut self) -> Result<Json, String>
+	{
+		match self.peek()
+		{
+			Some('{') =>
+			{
+				self.pos += 1;
+				let mut members = vec![];
+				if self.peek() == Some('}')
+				{
+					self.pos += 1;
+					return Ok(Json::Object(members));
+				}
+				loop
+				{
+					let key = self.string()?;
+					self.expect(':')?;
+					members.push((key, self.value()?));
+					if self.peek() == Some(',')
+					{
+						self.pos += 1;
+						continue;
+					}
+					self.expect('}')?;
+					return Ok(Json::Object(members));
+				}
+			},
+			Some('[') =>
+			{
+				self.pos += 1;
+				let mut elems = vec![];
+				if self.peek() == Some(']')
+				{
+					self.pos += 1;
+					return Ok(Json::Array(elems));
+				}
+				loop
+				{
+					elems.push(self.value()?);
+					if self.peek() == Some(',')
+					{
+						self.pos += 1;
+						continue;
+					}
+					self.expect(']')?;
+					return Ok(Json::Array(elems));
+				}
+			},
+			Some('"') => self.string().map(Json::String),
+			Some('t') => self.literal("true", Json::Bool(true)),
+			Some('f') => self.literal("false", Json::Bool(false)),
+			Some('n') => self.literal("null", Json::Null),
+			Some(_) => self.number(),
+			None => Err("Unexpected end of input".to_string()),
+		}
+	}
+}
+
+pub fn parse_json(src: &str) -> Result<Json, String>
+{
+	let chars: Vec<char> = src.chars().collect();
+	let mut parser = Parser
+	{
+		chars: &chars,
+		pos: 0,
+	};
+	let ret = parser.value()?;
+	if parser.peek().is_some()
+	{
+		return Err(format!("Trailing characters at {}", parser.pos));
+	}
+	Ok(ret)
+}
+
+#[test]
+fn basic()
+{
+	let json = parse_json(r#"{"a": [1, -2.5e1, true, null], "b": "x\"å"}"#).unwrap();
+	assert_eq!(Some(1.0), json.get("a").unwrap().as_array()[0].as_f64());
+	assert_eq!(Some(-25.0), json.get("a").unwrap().as_array()[1].as_f64());
+	assert_eq!(Json::Bool(true), json.get("a").unwrap().as_array()[2]);
+	assert_eq!(Some("x\"å"), json.get("b").unwrap().as_str());
+	assert!(parse_json("[1, 2").is_err());
+}
@@ -0,0 +1,353 @@
+// Loader for maps made in Tiled (http://www.mapeditor.org/), either as TMX or JSON.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use self::json::{parse_json, Json};
+use self::xml::{parse_xml, Element};
+
+pub mod json;
+pub mod xml;
+
+// Tiled stores flip flags in the high bits of the global tile ids.
+const GID_MASK: u32 = 0x1FFFFFFF;
+const DEFAULT_TERRAIN: &str = "ws";
+
+pub struct TiledObject
+{
+	pub tile_x: i32,
+	pub tile_y: i32,
+	pub properties: Vec<(String, String)>,
+}
+
+impl TiledObject
+{
+	pub fn property(&self, name: &str) -> Option<&str>
+	{
+		get_property(&self.properties, name)
+	}
+}
+
+pub struct TiledMap
+{
+	pub width: i32,
+	pub height: i32,
+	pub properties: Vec<(String, String)>,
+	// One terrain glyph per tile.
+	pub tiles: Vec<char>,
+	pub objects: Vec<TiledObject>,
+}
+
+pub fn get_property<'l>(properties: &'l [(String, String)], name: &str) -> Option<&'l str>
+{
+	properties.iter().find(|p| p.0 == name).map(|p| &p.1[..])
+}
+
+pub fn is_tiled_map(filename: &str) -> bool
+{
+	matches!(Path::new(filename).extension().and_then(|e| e.to_str()), Some("tmx") | Some("json"))
+}
+
+// In pixels.
+struct RawObject
+{
+	x: f32,
+	y: f32,
+	width: f32,
+	height: f32,
+	is_tile_object: bool,
+	properties: Vec<(String, String)>,
+}
+
+// Raw contents of a map, before the terrain glyphs are resolved.
+struct RawMap
+{
+	width: i32,
+	height: i32,
+	tile_width: i32,
+	tile_height: i32,
+	properties: Vec<(String, String)>,
+	gids: Vec<u32>,
+	// Glyphs set via a tile property, by global id.
+	tile_glyphs: HashMap<u32, char>,
+	first_gid: u32,
+	objects: Vec<RawObject>,
+}
+
+pub fn load_tiled(filename: &str) -> Result<TiledMap, String>
+{
+	let mut src = String::new();
+	File::open(filename).and_then(|mut f| f.read_to_string(&mut src)).map_err(|e| format!("Could not read {}: {}", filename, e))?;
+	let raw = if filename.ends_with(".tmx")
+	{
+		read_tmx(&src)
+	}
+	else
+	{
+		read_json(&src)
+	}.map_err(|e| format!("Could not load {}: {}", filename, e))?;
+
+	if raw.gids.len() as i32 != raw.width * raw.height
+	{
+		return Err(format!("Could not load {}: expected {} tiles, got {}", filename, raw.width * raw.height, raw.gids.len()));
+	}
+
+	let terrain: Vec<char> = get_property(&raw.properties, "terrain").unwrap_or(DEFAULT_TERRAIN).chars().collect();
+	let tiles = raw.gids.iter().map(|&gid|
+	{
+		let gid = gid & GID_MASK;
+		if let Some(&glyph) = raw.tile_glyphs.get(&gid)
+		{
+			glyph
+		}
+		else if gid >= raw.first_gid && gid > 0
+		{
+			*terrain.get((gid - raw.first_gid) as usize).unwrap_or(&'w')
+		}
+		else
+		{
+			'w'
+		}
+	}).collect();
+
+	let (tile_width, tile_height) = (raw.tile_width as f32, raw.tile_height as f32);
+	let objects = raw.objects.into_iter().map(|obj|
+	{
+		// Tile objects are anchored at their bottom-left corner.
+		let center_y = if obj.is_tile_object { obj.y - obj.height / 2.0 } else { obj.y + obj.height / 2.0 };
+		TiledObject
+		{
+			tile_x: ((obj.x + obj.width / 2.0) / tile_width).floor() as i32,
+			tile_y: (center_y / tile_height).floor() as i32,
+			properties: obj.properties,
+		}
+	}).collect();
+
+	Ok(TiledMap
+	{
+		width: raw.width,
+		height: raw.height,
+		properties: raw.properties,
+		tiles,
+		objects,
+	})
+}
+
+fn parse_attr<T: ::std::str::FromStr>(elem: &Element, name: &str) -> Result<T, String>
+{
+	elem.attr(name).ok_or(format!("'{}' is missing '{}'", elem.name, name))?
+		.parse().map_err(|_| format!("'{}' has an invalid '{}'", elem.name, name))
+}
+
+fn tmx_properties(elem: &Element) -> Vec<(String, String)>
+{
+	let mut ret = vec![];
+	if let Some(props) = elem.child("properties")
+	{
+		for prop in props.children_named("property")
+		{
+			// Multi-line values are stored as the element text.
+			let value = prop.attr("value").map(|v| v.to_string()).unwrap_or(prop.text.clone());
+			ret.push((prop.attr("name").unwrap_or("").to_string(), value));
+		}
+	}
+	ret
+}
+
+fn read_tmx(src: &str) -> Result<RawMap, String>
+{
+	let map = parse_xml(src)?;
+	if map.name != "map"
+	{
+		return Err("Root element is not 'map'".to_string());
+	}
+	let mut raw = RawMap
+	{
+		width: parse_attr(&map, "width")?,
+		height: parse_attr(&map, "height")?,
+		tile_width: parse_attr(&map, "tilewidth")?,
+		tile_height: parse_attr(&map, "tileheight")?,
+		properties: tmx_properties(&map),
+		gids: vec![],
+		tile_glyphs: HashMap::new(),
+		first_gid: 1,
+		objects: vec![],
+	};
+
+	for (i, tileset) in map.children_named("tileset").enumerate()
+	{
+		let first_gid: u32 = parse_attr(tileset, "firstgid")?;
+		if i == 0
+		{
+			raw.first_gid = first_gid;
+		}
+		for tile in tileset.children_named("tile")
+		{
+			let id: u32 = parse_attr(tile, "id")?;
+			if let Some(glyph) = get_property(&tmx_properties(tile), "glyph").and_then(|g| g.chars().next())
+			{
+				raw.tile_glyphs.insert(first_gid + id, glyph);
+			}
+		}
+	}
+
+	let layer = map.child("layer").ok_or("No tile layer")?;
+	let data = layer.child("data").ok_or("Tile layer has no data")?;
+	if data.attr("encoding") != Some("csv")
+	{
+		return Err("Only CSV tile layer encoding is supported".to_string());
+	}
+	for gid in data.text.split(',').map(|s| s.trim()).filter(|s| !s.is_empty())
+	{
+		raw.gids.push(gid.parse().map_err(|_| format!("Invalid tile '{}'", gid))?);
+	}
+
+	for group in map.children_named("objectgroup")
+	{
+		for obj in group.children_named("object")
+		{
+			raw.objects.push(RawObject
+			{
+				x: parse_attr(obj, "x")?,
+				y: parse_attr(obj, "y")?,
+				width: parse_attr(obj, "width").unwrap_or(0.0),
+				height: parse_attr(obj, "height").unwrap_or(0.0),
+				is_tile_object: obj.attr("gid").is_some(),
+				properties: tmx_properties(obj),
+			});
+		}
+	}
+	Ok(raw)
+}
+
+fn json_properties(elem: &Json) -> Vec<(String, String)>
+{
+	match elem.get("properties")
+	{
+		// Tiled 1.2+ uses an array of name/type/value objects.
+		Some(Json::Array(props)) => props.iter().map(|p|
+			(p.get("name").and_then(|n| n.as_str()).unwrap_or("").to_string(),
+			 p.get("value").map(|v| v.to_property_string()).unwrap_or_default())).collect(),
+		// Older versions use a plain object.
+		Some(Json::Object(props)) => props.iter().map(|p| (p.0.clone(), p.1.to_property_string())).collect(),
+		_ => vec![],
+	}
+}
+
+fn json_num(elem: &Json, name: &str) -> Result<f64, String>
+{
+	elem.get(name).and_then(|v| v.as_f64()).ok_or(format!("Missing '{}'", name))
+}
+
+fn read_json(src: &str) -> Result<RawMap, String>
+{
+	let map = parse_json(src)?;
+	let mut raw = RawMap
+	{
+		width: json_num(&map, "width")? as i32,
+		height: json_num(&map, "height")? as i32,
+		tile_width: json_num(&map, "tilewidth")? as i32,
+		tile_height: json_num(&map, "tileheight")? as i32,
+		properties: json_properties(&map),
+		gids: vec![],
+		tile_glyphs: HashMap::new(),
+		first_gid: 1,
+		objects: vec![],
+	};
+
+	for (i, tileset) in map.get("tilesets").map(|t| t.as_array()).unwrap_or(&[]).iter().enumerate()
+	{
+		let first_gid = json_num(tileset, "firstgid")? as u32;
+		if i == 0
+		{
+			raw.first_gid = first_gid;
+		}
+		for tile in tileset.get("tiles").map(|t| t.as_array()).unwrap_or(&[])
+		{
+			let id = json_num(tile, "id")? as u32;
+			if let Some(glyph) = get_property(&json_properties(tile), "glyph").and_then(|g| g.chars().next())
+			{
+				raw.tile_glyphs.insert(first_gid + id, glyph);
+			}
+		}
+	}
+
+	let layers = map.get("layers").map(|l| l.as_array()).unwrap_or(&[]);
+	let mut found_tiles = false;
+	for layer in layers
+	{
+		match layer.get("type").and_then(|t| t.as_str())
+		{
+			Some("tilelayer") if !found_tiles =>
+			{
+				found_tiles = true;
+				for gid in layer.get("data").map(|d| d.as_array()).unwrap_or(&[])
+				{
+					raw.gids.push(gid.as_f64().ok_or("Only CSV tile layer encoding is supported")? as u32);
+				}
+			},
+			Some("objectgroup") =>
+			{
+				for obj in layer.get("objects").map(|o| o.as_array()).unwrap_or(&[])
+				{
+					raw.objects.push(RawObject
+					{
+						x: json_num(obj, "x")? as f32,
+						y: json_num(obj, "y")? as f32,
+						width: json_num(obj, "width").unwrap_or(0.0) as f32,
+						height: json_num(obj, "height").unwrap_or(0.0) as f32,
+						is_tile_object: obj.get("gid").is_some(),
+						properties: json_properties(obj),
+					});
+				}
+			},
+			_ => ()
+		}
+	}
+	if !found_tiles
+	{
+		return Err("No tile layer".to_string());
+	}
+	Ok(raw)
+}
+
+#[cfg(test)]
+fn check_sample(map: &TiledMap)
+{
+	assert_eq!((4, 3), (map.width, map.height));
+	assert_eq!("wsswwsiwwssw", map.tiles.iter().collect::<String>());
+	assert_eq!(Some("5"), get_property(&map.properties, "seed"));
+	assert_eq!(Some("Stop"), get_property(&map.properties, "zone_of_control"));
+	assert_eq!(2, map.objects.len());
+	let unit = &map.objects[0];
+	assert_eq!((1, 1), (unit.tile_x, unit.tile_y));
+	assert_eq!(Some("data/wizard.cfg"), unit.property("unit"));
+	assert_eq!(Some("ours"), unit.property("team"));
+	let circle = &map.objects[1];
+	assert_eq!((2, 0), (circle.tile_x, circle.tile_y));
+	assert_eq!(Some("frost"), circle.property("word"));
+}
+
+#[test]
+fn sample_tmx()
+{
+	let mut src = String::new();
+	File::open("data/tiled_sample.tmx").unwrap().read_to_string(&mut src).unwrap();
+	let raw = read_tmx(&src).unwrap();
+	assert_eq!((32, 32), (raw.tile_width, raw.tile_height));
+	assert_eq!(Some(&'i'), raw.tile_glyphs.get(&3));
+	check_sample(&load_tiled("data/tiled_sample.tmx").unwrap());
+}
+
+#[test]
+fn sample_json()
+{
+	let mut src = String::new();
+	File::open("data/tiled_sample.json").unwrap().read_to_string(&mut src).unwrap();
+	let raw = read_json(&src).unwrap();
+	assert_eq!((32, 32), (raw.tile_width, raw.tile_height));
+	assert_eq!(Some(&'i'), raw.tile_glyphs.get(&3));
+	check_sample(&load_tiled("data/tiled_sample.json").unwrap());
+}
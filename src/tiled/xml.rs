@@ -0,0 +1,207 @@
+// Just enough XML to read Tiled's TMX files: elements, attributes and text.
+
+#[derive(Clone, Debug)]
+pub struct Element
+{
+	pub name: String,
+	pub attrs: Vec<(String, String)>,
+	pub children: Vec<Element>,
+	pub text: String,
+}
+
+impl Element
+{
+	pub fn attr(&self, name: &str) -> Option<&str>
+	{
+		self.attrs.iter().find(|a| a.0 == name).map(|a| &a.1[..])
+	}
+
+	pub fn children_named<'l>(&'l self, name: &'l str) -> Box<dyn Iterator<Item = &'l Element> + 'l>
+	{
+		Box::new(self.children.iter().filter(move |c| c.name == name))
+	}
+
+	pub fn child(&self, name: &str) -> Option<&Element>
+	{
+		self.children.iter().find(|c| c.name == name)
+	}
+}
+
+fn unescape(s: &str) -> String
+{
+	s.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+struct Parser<'l>
+{
+	src: &'l str,
+	pos: usize,
+}
+
+impl<'l> Parser<'l>
+{
+	fn rest(&self) -> &'l str
+	{
+		&self.src[self.pos..]
+	}
+
+	fn skip_whitespace(&mut self)
+	{
+		let rest = self.rest();
+		self.pos += rest.len() - rest.trim_start().len();
+	}
+
+	fn skip_past(&mut self, end: &str) -> Result<(), String>
+	{
+		match self.rest().find(end)
+		{
+			Some(idx) =>
+			{
+				self.pos += idx + end.len();
+				Ok(())
+			},
+			None => Err(format!("Expected '{}'", end)),
+		}
+	}
+
+	fn name(&mut self) -> String
+	{
+		let rest = self.rest();
+		let len = rest.find(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '=').unwrap_or(rest.len());
+		self.pos += len;
+		rest[..len].to_string()
+	}
+
+	// Skips declarations, comments and whitespace between elements, collecting any text.
+	fn misc(&mut self, text: &mut String) -> Result<(), String>
+	{
+		loop
+		{
+			let rest = self.rest();
+			if rest.starts_with("<?")
+			{
+				self.skip_past("?>")?;
+			}
+			else if rest.starts_with("<!--")
+			{
+				self.skip_past("-->")?;
+			}
+			else if rest.starts_with("<!")
+			{
+				self.skip_past(">")?;
+			}
+			else if rest.starts_with('<') || rest.is_empty()
+			{
+				return Ok(());
+			}
+			else
+			{
+				let len = rest.find('<').unwrap_or(rest.len());
+				text.push_str(&unescape(&rest[..len]));
+				self.pos += len;
+			}
+		}
+	}
+
+	fn element(&mut self) -> Result<Element, String>
+	{
+		if !self.rest().starts_with('<')
+		{
+			return Err(format!("Expected an element at {}", self.pos));
+		}
+		self.pos += 1;
+		let mut elem = Element
+		{
+			name: self.name(),
+			attrs: vec![],
+			children: vec![],
+			text: String::new(),
+		};
+		loop
+		{
+			self.skip_whitespace();
+			let rest = self.rest();
+			if rest.starts_with("/>")
+			{
+				self.pos += 2;
+				return Ok(elem);
+			}
+			else if rest.starts_with('>')
+			{
+				self.pos += 1;
+				break;
+			}
+			else if rest.is_empty()
+			{
+				return Err(format!("Unterminated element '{}'", elem.name));
+			}
+			let attr_name = self.name();
+			self.skip_whitespace();
+			if !self.rest().starts_with('=')
+			{
+				return Err(format!("Expected '=' after '{}' at {}", attr_name, self.pos));
+			}
+			self.pos += 1;
+			self.skip_whitespace();
+			let quote = self.rest().chars().next().ok_or("Unexpected end of input")?;
+			if quote != '"' && quote != '\''
+			{
+				return Err(format!("Expected a quoted value at {}", self.pos));
+			}
+			self.pos += 1;
+			let len = self.rest().find(quote).ok_or("Unterminated attribute value")?;
+			let value = unescape(&self.rest()[..len]);
+			self.pos += len + 1;
+			elem.attrs.push((attr_name, value));
+		}
+		loop
+		{
+			self.misc(&mut elem.text)?;
+			let rest = self.rest();
+			if rest.is_empty()
+			{
+				return Err(format!("Unterminated element '{}'", elem.name));
+			}
+			if rest.starts_with("</")
+			{
+				self.pos += 2;
+				let name = self.name();
+				if name != elem.name
+				{
+					return Err(format!("Expected '</{}>', got '</{}>'", elem.name, name));
+				}
+				self.skip_past(">")?;
+				return Ok(elem);
+			}
+			let child = self.element()?;
+			elem.children.push(child);
+		}
+	}
+}
+
+pub fn parse_xml(src: &str) -> Result<Element, String>
+{
+	let mut parser = Parser
+	{
+		src,
+		pos: 0,
+	};
+	let mut text = String::new();
+	parser.misc(&mut text)?;
+	parser.element()
+}
+
+#[test]
+fn basic()
+{
+	let root = parse_xml(r#"<?xml version="1.0"?>
+<!-- comment -->
+<map width="2"><layer name='a &amp; b'><data encoding="csv">1,2</data></layer><objectgroup/></map>"#).unwrap();
+	assert_eq!("map", root.name);
+	assert_eq!(Some("2"), root.attr("width"));
+	let layer = root.child("layer").unwrap();
+	assert_eq!(Some("a & b"), layer.attr("name"));
+	assert_eq!("1,2", layer.child("data").unwrap().text);
+	assert_eq!(1, root.children_named("objectgroup").count());
+	assert!(parse_xml("<map><layer></map>").is_err());
+}
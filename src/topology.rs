@@ -0,0 +1,191 @@
+use std::cmp::{min, max};
+
+slr_def!
+{
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	pub enum Topology
+	{
+		// 4 neighbours.
+		Square,
+		// 8 neighbours, diagonal steps cost `diagonal_cost` action points.
+		Diagonal,
+		// 6 neighbours, with the odd rows shifted half a tile to the right.
+		Hex
+	}
+}
+
+const SQUARE_OFFSETS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const DIAGONAL_OFFSETS: [(i32, i32); 8] = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (1, 1), (-1, 1)];
+const HEX_EVEN_OFFSETS: [(i32, i32); 6] = [(-1, 0), (1, 0), (-1, -1), (0, -1), (-1, 1), (0, 1)];
+const HEX_ODD_OFFSETS: [(i32, i32); 6] = [(-1, 0), (1, 0), (0, -1), (1, -1), (0, 1), (1, 1)];
+
+pub fn neighbour_offsets(topology: Topology, y: i32) -> &'static [(i32, i32)]
+{
+	match topology
+	{
+		Topology::Square => &SQUARE_OFFSETS,
+		Topology::Diagonal => &DIAGONAL_OFFSETS,
+		Topology::Hex => if y % 2 == 0 { &HEX_EVEN_OFFSETS } else { &HEX_ODD_OFFSETS },
+	}
+}
+
+fn to_cube(x: i32, y: i32) -> (i32, i32, i32)
+{
+	let q = x - (y - (y & 1)) / 2;
+	(q, y, -q - y)
+}
+
+// Number of steps between two tiles, ignoring obstacles.
+pub fn tile_dist(topology: Topology, x1: i32, y1: i32, x2: i32, y2: i32) -> i32
+{
+	let dx = (x1 - x2).abs();
+	let dy = (y1 - y2).abs();
+	match topology
+	{
+		Topology::Square => dx + dy,
+		Topology::Diagonal => max(dx, dy),
+		Topology::Hex =>
+		{
+			let (q1, r1, s1) = to_cube(x1, y1);
+			let (q2, r2, s2) = to_cube(x2, y2);
+			max((q1 - q2).abs(), max((r1 - r2).abs(), (s1 - s2).abs()))
+		},
+	}
+}
+
+pub fn is_diagonal_step(topology: Topology, dx: i32, dy: i32) -> bool
+{
+	topology == Topology::Diagonal && dx != 0 && dy != 0
+}
+
+pub fn step_cost(topology: Topology, diagonal_cost: i32, dx: i32, dy: i32) -> i32
+{
+	if is_diagonal_step(topology, dx, dy) { diagonal_cost } else { 1 }
+}
+
+// A lower bound on the cost of moving between two tiles, for A*.
+pub fn cost_estimate(topology: Topology, diagonal_cost: i32, x1: i32, y1: i32, x2: i32, y2: i32) -> i32
+{
+	if topology == Topology::Diagonal
+	{
+		let dx = (x1 - x2).abs();
+		let dy = (y1 - y2).abs();
+		// A diagonal step is never worse than two straight ones.
+		let diagonals = min(dx, dy);
+		max(dx, dy) - diagonals + diagonals * min(diagonal_cost, 2)
+	}
+	else
+	{
+		tile_dist(topology, x1, y1, x2, y2)
+	}
+}
+
+// How far a row is pushed to the right, in tiles. Hex maps push the odd rows half a tile.
+pub fn row_shift(topology: Topology, y: i32) -> f32
+{
+	if topology == Topology::Hex && y % 2 != 0 { 0.5 } else { 0.0 }
+}
+
+// Where the centre of a tile is in the world.
+pub fn tile_to_world(topology: Topology, x: i32, y: i32) -> (f32, f32)
+{
+	((x as f32 + row_shift(topology, y)) * 32.0, y as f32 * 32.0)
+}
+
+// The tile drawn under a point in the world.
+pub fn world_to_tile(topology: Topology, x: f32, y: f32) -> (i32, i32)
+{
+	let tile_y = ((y + 16.0) / 32.0).floor() as i32;
+	(((x + 16.0) / 32.0 - row_shift(topology, tile_y)).floor() as i32, tile_y)
+}
+
+// The tiles a straight line between two tile centres passes through, both ends included. A line
+// going exactly through a corner only touches the tiles on either end of it.
+pub fn grid_line(x1: i32, y1: i32, x2: i32, y2: i32) -> Vec<(i32, i32)>
+{
+	let dx = (x2 - x1).abs();
+	let dy = (y2 - y1).abs();
+	let step_x = (x2 - x1).signum();
+	let step_y = (y2 - y1).signum();
+	let mut ret = Vec::with_capacity((dx + dy + 1) as usize);
+	let (mut x, mut y) = (x1, y1);
+	// Doubled, to keep the crossing points on the tile edges integral.
+	let mut error = dx - dy;
+	ret.push((x, y));
+	while (x, y) != (x2, y2)
+	{
+		if error > 0
+		{
+			x += step_x;
+			error -= 2 * dy;
+		}
+		else if error < 0
+		{
+			y += step_y;
+			error += 2 * dx;
+		}
+		else
+		{
+			x += step_x;
+			y += step_y;
+			error += 2 * dx - 2 * dy;
+		}
+		ret.push((x, y));
+	}
+	ret
+}
+
+#[test]
+fn hex_dist()
+{
+	for &(x, y) in &[(3, 4), (3, 5)]
+	{
+		for &(dx, dy) in neighbour_offsets(Topology::Hex, y)
+		{
+			assert_eq!(1, tile_dist(Topology::Hex, x, y, x + dx, y + dy));
+		}
+	}
+	assert_eq!(3, tile_dist(Topology::Hex, 0, 0, 3, 0));
+	assert_eq!(2, tile_dist(Topology::Hex, 0, 0, 1, 2));
+	assert_eq!(2, tile_dist(Topology::Hex, 1, 1, 0, 3));
+}
+
+#[test]
+fn hex_picking()
+{
+	for &topology in &[Topology::Square, Topology::Hex]
+	{
+		for y in 0..4
+		{
+			for x in 0..4
+			{
+				let (wx, wy) = tile_to_world(topology, x, y);
+				assert_eq!((x, y), world_to_tile(topology, wx, wy));
+				assert_eq!((x, y), world_to_tile(topology, wx + 15.0, wy - 15.0));
+			}
+		}
+	}
+	// Half way along an odd row's tile is the seam between two of the even row's.
+	assert_eq!((1, 1), world_to_tile(Topology::Hex, 32.0, 32.0));
+	assert_eq!((1, 0), world_to_tile(Topology::Hex, 32.0, 0.0));
+	assert_eq!((0, 1), world_to_tile(Topology::Hex, 15.0, 32.0));
+	// Every neighbour is right next to the tile on screen.
+	for &y in &[4, 5]
+	{
+		let (x0, y0) = tile_to_world(Topology::Hex, 3, y);
+		for &(dx, dy) in neighbour_offsets(Topology::Hex, y)
+		{
+			let (x1, y1) = tile_to_world(Topology::Hex, 3 + dx, y + dy);
+			assert!((x1 - x0).abs() <= 32.0 && (y1 - y0).abs() <= 32.0);
+		}
+	}
+}
+
+#[test]
+fn lines()
+{
+	assert_eq!(vec![(0, 0), (1, 0), (2, 0)], grid_line(0, 0, 2, 0));
+	assert_eq!(vec![(2, 2), (1, 1), (0, 0)], grid_line(2, 2, 0, 0));
+	assert_eq!(vec![(0, 0), (1, 0), (1, 1), (2, 1)], grid_line(0, 0, 2, 1));
+	assert_eq!(vec![(0, 0), (0, 1), (1, 1), (1, 2), (1, 3), (2, 3), (2, 4)], grid_line(0, 0, 2, 4));
+}
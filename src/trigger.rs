@@ -0,0 +1,268 @@
+use game_state::*;
+use map::{map_tile_solid, xy_to_index, set_tile};
+use unit::create_unit;
+use effect::create_spawn;
+use announce::{create_announcement, announce_lines};
+use undo::forget_undo;
+use topology::tile_to_world;
+use std::mem;
+
+slr_def!
+{
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	pub enum TriggerCondition
+	{
+		TurnStart,
+		UnitEnters,
+		EnemyDies,
+		SpellCast,
+		ManaReaches
+	}
+}
+
+slr_def!
+{
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	pub enum TriggerActionKind
+	{
+		Spawn,
+		Announce,
+		Dialogue,
+		SetTile,
+		GrantWord,
+		Win,
+		Lose
+	}
+}
+
+slr_def!
+{
+	#[derive(Clone, Debug)]
+    pub struct TriggerActionConfig
+    {
+        kind: TriggerActionKind = TriggerActionKind::Announce,
+        unit: String = String::new(),
+        ours: i32 = 0,
+        x: i32 = 0,
+        y: i32 = 0,
+        text: String = String::new(),
+        tile: String = String::new(),
+        word: String = String::new()
+    }
+}
+
+impl Default for TriggerActionConfig
+{
+	fn default() -> TriggerActionConfig
+	{
+		TriggerActionConfig::new()
+	}
+}
+
+slr_def!
+{
+	#[derive(Clone, Debug)]
+    pub struct TriggerConfig
+    {
+        condition: TriggerCondition = TriggerCondition::TurnStart,
+        turn: i32 = 0,
+        mana: i32 = 0,
+        // Region for UnitEnters, inclusive.
+        x1: i32 = 0,
+        y1: i32 = 0,
+        x2: i32 = 0,
+        y2: i32 = 0,
+        ours: i32 = 1,
+        // Optional unit name filter for UnitEnters, EnemyDies and SpellCast.
+        unit: String = String::new(),
+        repeat: i32 = 0,
+        actions: Vec<TriggerActionConfig> = vec![]
+    }
+}
+
+impl Default for TriggerConfig
+{
+	fn default() -> TriggerConfig
+	{
+		TriggerConfig::new()
+	}
+}
+
+#[derive(Clone, Debug)]
+pub enum GameEvent
+{
+	TurnStart(i32),
+	UnitDied(String, bool),
+	SpellCast(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Trigger
+{
+	pub config: TriggerConfig,
+	fired: bool,
+	// Level conditions only fire when they become true.
+	was_true: bool,
+}
+
+impl Trigger
+{
+	pub fn new(config: TriggerConfig) -> Trigger
+	{
+		Trigger
+		{
+			config,
+			fired: false,
+			was_true: false,
+		}
+	}
+}
+
+fn name_matches(filter: &str, name: &str) -> bool
+{
+	filter.is_empty() || filter == name
+}
+
+fn run_action(action: &TriggerActionConfig, map_data: &mut MapData, state: &mut GameState)
+{
+	let map_id = state.current_map_id;
+	match action.kind
+	{
+		TriggerActionKind::Spawn =>
+		{
+			if map_tile_solid(action.x, action.y, map_data)
+			{
+				warn!("Could not spawn {} at {} {}, the tile is blocked.", action.unit, action.x, action.y);
+				return;
+			}
+			let mut obj = create_unit(map_id, &action.unit, action.ours != 0, state);
+			let (world_x, world_y) = tile_to_world(map_data.topology, action.x, action.y);
+			obj.x = world_x;
+			obj.y = world_y;
+			obj.tile_x = action.x;
+			obj.tile_y = action.y;
+			let idx = xy_to_index(action.x, action.y, map_data.width, map_data.height);
+			map_data.collision_map[idx] = Some(action.ours != 0);
+			map_data.generation = map_data.generation.wrapping_add(1);
+			let effect = create_spawn(map_id, obj.x, obj.y, state);
+			state.add_object(obj);
+			state.add_object(effect);
+		},
+		TriggerActionKind::Announce =>
+		{
+			let announce = create_announcement(map_id, &action.text, state);
+			state.add_object(announce);
+		},
+		TriggerActionKind::Dialogue =>
+		{
+			announce_lines(map_id, &action.text, 2.0, state);
+		},
+		TriggerActionKind::SetTile =>
+		{
+			if let Some(tile) = action.tile.chars().next()
+			{
+				set_tile(action.x, action.y, tile, map_data);
+			}
+		},
+		TriggerActionKind::GrantWord =>
+		{
+			map_data.base_inventory.push(action.word.clone());
+		},
+		TriggerActionKind::Win =>
+		{
+			map_data.scripted_end = Some(true);
+		},
+		TriggerActionKind::Lose =>
+		{
+			map_data.scripted_end = Some(false);
+		},
+	}
+}
+
+pub struct TriggerLogic;
+
+complex_behavior!
+{
+	TriggerLogic[obj.is_map] |self, obj, objects, state|
+	{
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let mut map_data = map_data.borrow_mut();
+			let events = mem::take(&mut state.events);
+			let mut triggers = mem::take(&mut map_data.triggers);
+			for trigger in &mut triggers
+			{
+				if trigger.fired && trigger.config.repeat == 0
+				{
+					continue;
+				}
+				let c = &trigger.config;
+				let mut num_fires = match c.condition
+				{
+					TriggerCondition::TurnStart =>
+					{
+						events.iter().filter(|e| match **e
+						{
+							GameEvent::TurnStart(turn) => turn == c.turn,
+							_ => false,
+						}).count()
+					},
+					TriggerCondition::EnemyDies =>
+					{
+						events.iter().filter(|e| match **e
+						{
+							GameEvent::UnitDied(ref name, ours) => !ours && name_matches(&c.unit, name),
+							_ => false,
+						}).count()
+					},
+					TriggerCondition::SpellCast =>
+					{
+						events.iter().filter(|e| match **e
+						{
+							GameEvent::SpellCast(ref name) => name_matches(&c.unit, name),
+							_ => false,
+						}).count()
+					},
+					TriggerCondition::UnitEnters | TriggerCondition::ManaReaches =>
+					{
+						let is_true = if c.condition == TriggerCondition::UnitEnters
+						{
+							objects.elems().iter().any(|obj| obj.can_act && obj.has_pos && obj.is_ours == (c.ours != 0) &&
+								obj.tile_x >= c.x1 && obj.tile_x <= c.x2 && obj.tile_y >= c.y1 && obj.tile_y <= c.y2 &&
+								obj.has_name && name_matches(&c.unit, &obj.name))
+						}
+						else
+						{
+							map_data.mana >= c.mana
+						};
+						let fire = is_true && !trigger.was_true;
+						trigger.was_true = is_true;
+						fire as usize
+					},
+				};
+				if c.repeat == 0
+				{
+					num_fires = num_fires.min(1);
+				}
+				for _ in 0..num_fires
+				{
+					info!("Trigger fired: {:?}", c.condition);
+					trigger.fired = true;
+					forget_undo(&mut map_data);
+					for action in &c.actions
+					{
+						run_action(action, &mut map_data, state);
+					}
+				}
+			}
+			map_data.triggers = triggers;
+		}
+	}
+}
+
+pub fn create_triggers(configs: &[TriggerConfig]) -> Vec<Trigger>
+{
+	configs.iter().map(|c| Trigger::new(c.clone())).collect()
+}
+
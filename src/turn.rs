@@ -1,7 +1,31 @@
-use engine::id_map::HasId;
+use engine::id_map::{IdMap, HasId};
+use game_state::{Object, GameState};
 use path::order_unit;
-use util::l1_dist;
+use topology::tile_dist;
 use announce::create_announcement;
+use trigger::GameEvent;
+use status::tick_statuses;
+use ability::tick_cooldowns;
+use experience::{gain_experience, TURN_EXPERIENCE};
+
+// Gets one side ready for its phase. Statuses tick here, so a status lasting a turn lasts one of
+// the unit's own phases.
+pub fn start_phase(objects: &mut IdMap<Object>, ours: bool, state: &mut GameState)
+{
+	for obj in objects.elems_mut()
+	{
+		if obj.can_act && obj.is_ours == ours
+		{
+			obj.action_points = obj.max_action_points;
+			tick_statuses(obj, state);
+			tick_cooldowns(obj);
+			if obj.health > 0
+			{
+				gain_experience(obj, TURN_EXPERIENCE, state);
+			}
+		}
+	}
+}
 
 pub struct TurnLogic;
 
@@ -19,19 +43,19 @@ complex_behavior!
 				let mut order_location = None;
 				for enemy_obj in objects.elems()
 				{
-					if !enemy_obj.is_ours && enemy_obj.can_act && enemy_obj.action_points > 0
+					if !enemy_obj.is_ours && enemy_obj.can_act && enemy_obj.action_points > 0 && enemy_obj.health > 0
 					{
 						for our_obj in objects.elems()
 						{
 							if our_obj.is_ours && our_obj.can_act
 							{
 								object_to_order = Some(enemy_obj.get_id());
-								let cand_dist = l1_dist(our_obj.tile_x, our_obj.tile_y, enemy_obj.tile_x, enemy_obj.tile_y);
+								let cand_dist = tile_dist(map_data.topology, our_obj.tile_x, our_obj.tile_y, enemy_obj.tile_x, enemy_obj.tile_y);
 								if cand_dist < enemy_obj.sight_range
 								{
 									if let Some((x, y)) = order_location
 									{
-										if cand_dist < l1_dist(x, y, enemy_obj.tile_x, enemy_obj.tile_y)
+										if cand_dist < tile_dist(map_data.topology, x, y, enemy_obj.tile_x, enemy_obj.tile_y)
 										{
 											order_location = Some((our_obj.tile_x, our_obj.tile_y));
 										}
@@ -59,15 +83,10 @@ complex_behavior!
 				}
 				else
 				{
-					for obj in objects.elems_mut()
-					{
-						if obj.can_act
-						{
-							obj.action_points = obj.max_action_points;
-						}
-					}
+					start_phase(objects, true, state);
 					map_data.our_turn = true;
 					map_data.turn += 1;
+					state.events.push(GameEvent::TurnStart(map_data.turn));
 					map_data.mana += 2 + map_data.num_circles_held;
 					let announce = create_announcement(state.current_map_id, &format!("Turn {} begins!", map_data.turn), state);
 					state.add_object(announce);
@@ -0,0 +1,140 @@
+// Taking back the player's actions within a turn.
+
+use allegro::*;
+use engine::id_map::{IdMap, HasId};
+use game_state::*;
+use map::set_tile;
+use announce::create_announcement;
+use status::Status;
+use ability::Ability;
+use topology::world_to_tile;
+use path::clear_orders;
+
+struct UnitState
+{
+	id: usize,
+	x: f32,
+	y: f32,
+	action_points: i32,
+	health: i32,
+	statuses: Vec<Status>,
+	abilities: Vec<Ability>,
+}
+
+// How things were before one of the player's actions.
+pub struct UndoState
+{
+	units: Vec<UnitState>,
+	mana: i32,
+	base_inventory: Vec<String>,
+	tiles: Vec<char>,
+}
+
+fn is_unit(obj: &Object) -> bool
+{
+	obj.can_act && obj.has_pos
+}
+
+pub fn save_undo(objects: &IdMap<Object>, map_data: &mut MapData)
+{
+	let units = objects.elems().iter().filter(|obj| is_unit(obj)).map(|obj| UnitState
+	{
+		id: obj.get_id(),
+		x: obj.x,
+		y: obj.y,
+		action_points: obj.action_points,
+		health: obj.health,
+		statuses: obj.statuses.clone(),
+		abilities: obj.abilities.clone(),
+	}).collect();
+	let undo_state = UndoState
+	{
+		units,
+		mana: map_data.mana,
+		base_inventory: map_data.base_inventory.clone(),
+		tiles: map_data.tiles.clone(),
+	};
+	map_data.undo_stack.push(undo_state);
+}
+
+// Called once something happened that the player shouldn't get to take back, like an attack
+// landing or anything random being revealed.
+pub fn forget_undo(map_data: &mut MapData)
+{
+	map_data.undo_stack.clear();
+}
+
+fn undo(objects: &mut IdMap<Object>, map_data: &mut MapData, state: &mut GameState) -> bool
+{
+	let undo_state = match map_data.undo_stack.pop()
+	{
+		Some(undo_state) => undo_state,
+		None => return false,
+	};
+	for obj in objects.elems_mut()
+	{
+		if !is_unit(obj)
+		{
+			continue;
+		}
+		match undo_state.units.iter().find(|u| u.id == obj.get_id())
+		{
+			Some(unit) =>
+			{
+				clear_orders(obj, map_data.topology);
+				obj.x = unit.x;
+				obj.y = unit.y;
+				let (tile_x, tile_y) = world_to_tile(map_data.topology, unit.x, unit.y);
+				obj.tile_x = tile_x;
+				obj.tile_y = tile_y;
+				obj.action_points = unit.action_points;
+				obj.health = unit.health;
+				obj.statuses = unit.statuses.clone();
+				obj.abilities = unit.abilities.clone();
+			},
+			// Summoned since.
+			None if obj.is_ours => state.remove_object(obj.get_id()),
+			None => (),
+		}
+	}
+	map_data.generation = map_data.generation.wrapping_add(1);
+	map_data.mana = undo_state.mana;
+	map_data.base_inventory = undo_state.base_inventory;
+	for (idx, &tile) in undo_state.tiles.iter().enumerate()
+	{
+		let (x, y) = (idx as i32 % map_data.width, idx as i32 / map_data.width);
+		set_tile(x, y, tile, map_data);
+	}
+	true
+}
+
+pub struct UndoInput;
+
+complex_behavior!
+{
+	UndoInput[obj.is_map] |self, obj, objects, state|
+	{
+		if state.key_down != Some(KeyCode::U)
+		{
+			return;
+		}
+		let map_data = objects.get(state.current_map_id).map(|obj| obj.map_data.clone());
+		if let Some(ref map_data) = map_data
+		{
+			let mut map_data = map_data.borrow_mut();
+			if !map_data.our_turn || map_data.executing_orders || map_data.crafting
+			{
+				return;
+			}
+			if undo(objects, &mut map_data, state)
+			{
+				info!("Undid a move, {} left", map_data.undo_stack.len());
+			}
+			else
+			{
+				let announce = create_announcement(state.current_map_id, "Nothing to undo!", state);
+				state.add_object(announce);
+			}
+		}
+	}
+}
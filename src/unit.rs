@@ -6,6 +6,11 @@ use game_state::*;
 use animation::Animation;
 use map::xy_to_index;
 use effect::create_death;
+use trigger::GameEvent;
+use combat::{DamageType, Resistances};
+use ability::{Ability, AbilityConfig};
+use experience::{Growth, experience_needed};
+use topology::{Topology, world_to_tile};
 
 slr_def!
 {
@@ -17,7 +22,30 @@ slr_def!
         health: i32 = 4,
         sight_range: i32 = 4,
         damage: i32 = 1,
-        fire: i32 = 0,
+        damage_type: DamageType = DamageType::Physical,
+        // Taken off every physical hit.
+        armour: i32 = 0,
+        resistances: Resistances = Resistances::new(),
+        // The chance to hit is accuracy minus the target's evasion, in percent.
+        accuracy: i32 = 100,
+        evasion: i32 = 0,
+        // Critical hits do double damage.
+        crit_chance: i32 = 0,
+        // Each attack does up to this much more or less than damage.
+        damage_spread: i32 = 0,
+        // How hard the unit strikes back at its attackers, in percent of its damage.
+        retaliation: i32 = 100,
+        // Whether the unit's targets are kept from striking back.
+        no_retaliation: i32 = 0,
+        // In tiles. Units with a max_range over 1 attack with projectiles.
+        min_range: i32 = 1,
+        max_range: i32 = 1,
+        ignore_zone_of_control: i32 = 0,
+        abilities: Vec<AbilityConfig> = vec![],
+        // Each level takes this much more experience than the last.
+        experience_per_level: i32 = 10,
+        // What each level after the first gives, the unit stops levelling at the end of it.
+        growth: Vec<Growth> = vec![],
         name: String = "unit".to_string()
     }
 }
@@ -30,6 +58,7 @@ pub fn create_unit(parent: usize, file: &str, ours: bool, state: &mut GameState)
 	let mut obj = Object::new(state.new_id());
 	obj.has_name = true;
 	obj.name = config.name.clone();
+	obj.unit_file = file.to_string();
 	obj.parent = parent;
 	obj.has_pos = true;
 	obj.has_vel = true;
@@ -41,11 +70,26 @@ pub fn create_unit(parent: usize, file: &str, ours: bool, state: &mut GameState)
 	obj.health = config.health;
 	obj.max_health = config.health;
 	obj.is_ours = ours;
-	obj.fire = config.fire != 0;
+	obj.damage_type = config.damage_type;
+	obj.armour = config.armour;
+	obj.resistances = config.resistances.clone();
+	obj.accuracy = config.accuracy;
+	obj.evasion = config.evasion;
+	obj.crit_chance = config.crit_chance;
+	// A negative spread would make no sense, and can't be rolled.
+	obj.damage_spread = config.damage_spread.max(0);
+	obj.retaliation = config.retaliation;
+	obj.no_retaliation = config.no_retaliation != 0;
+	obj.min_range = config.min_range;
+	obj.max_range = config.max_range;
+	obj.ignores_zone_of_control = config.ignore_zone_of_control != 0;
 	obj.max_action_points = config.action_points;
 	obj.action_points = config.action_points;
 	obj.sight_range = config.sight_range;
 	obj.damage = config.damage;
+	obj.abilities = config.abilities.iter().cloned().map(Ability::new).collect();
+	obj.experience_per_level = config.experience_per_level;
+	obj.growth = config.growth.clone();
 	obj.sprite = Some(Animation::new(&config.sprite, false, state));
 	
 	obj
@@ -67,11 +111,34 @@ pub fn create_magic_circle(parent: usize, word: &str, state: &mut GameState) ->
 	obj
 }
 
+// Where to draw the object, between its positions at the last two logic ticks.
+pub fn draw_pos(obj: &Object, state: &GameState) -> (f32, f32)
+{
+	let (dx, dy) = (obj.x - obj.old_x, obj.y - obj.old_y);
+	// Anything that went further than this in one tick was placed there, rather than moved.
+	if dx.abs() > 16.0 || dy.abs() > 16.0
+	{
+		return (obj.x, obj.y);
+	}
+	let f = state.draw_interp.clamp(0.0, 1.0);
+	(obj.old_x + dx * f, obj.old_y + dy * f)
+}
+
+simple_behavior!
+{
+	InterpLogic[obj.has_pos] |obj, _state|
+	{
+		obj.old_x = obj.x;
+		obj.old_y = obj.y;
+	}
+}
+
 simple_behavior!
 {
 	UnitDraw[obj.has_sprite && obj.has_pos && !obj.is_effect && !obj.is_magic_circle] |obj, state|
 	{
-		obj.sprite.as_ref().unwrap().draw(obj.x - 16.0, obj.y - 16.0, state);
+		let (x, y) = draw_pos(obj, state);
+		obj.sprite.as_ref().unwrap().draw_flipped(x - 16.0, y - 16.0, obj.facing_right, state);
 	}
 }
 
@@ -100,16 +167,27 @@ simple_behavior!
 			state.enemy_flag.as_ref().unwrap()
 		};
 		
-		ani.draw(obj.x - 16.0, obj.y - 16.0, state);
+		let (x, y) = draw_pos(obj, state);
+		ani.draw(x - 16.0, y - 16.0, state);
 	}
 }
 
-simple_behavior!
+pub struct UnitLogic;
+
+complex_behavior!
 {
-	UnitLogic[obj.has_pos] |obj, _state|
+	UnitLogic[obj.has_pos] |self, obj, objects, state|
 	{
-		obj.tile_x = (obj.x as i32 + 16) / 32;
-		obj.tile_y = (obj.y as i32 + 16) / 32;
+		let topology = objects.get(state.current_map_id).map_or(Topology::Square, |map| map.map_data.borrow().topology);
+		for obj in objects.elems_mut()
+		{
+			if self.check_object(obj)
+			{
+				let (tile_x, tile_y) = world_to_tile(topology, obj.x, obj.y);
+				obj.tile_x = tile_x;
+				obj.tile_y = tile_y;
+			}
+		}
 	}
 }
 
@@ -120,6 +198,7 @@ simple_behavior!
 		if obj.health <= 0
 		{
 			state.remove_object(obj.get_id());
+			state.events.push(GameEvent::UnitDied(obj.name.clone(), obj.is_ours));
 			let death = create_death(state.current_map_id, obj.x, obj.y, state);
 			state.add_object(death);
 		}
@@ -131,7 +210,7 @@ simple_behavior!
 	SelectedDraw[obj.is_selectable && obj.selected] |obj, state|
 	{
 		let x = state.buffer.get_width() - 80;
-		let mut y = state.buffer.get_height() - 48;
+		let mut y = state.buffer.get_height() - 58 - 10 * obj.statuses.len() as i32;
 		if obj.has_name
 		{
 			state.core.draw_text(&state.ui_font, Color::from_rgba(224, 224, 224, 255),
@@ -150,6 +229,25 @@ simple_behavior!
 		{
 			state.core.draw_text(&state.ui_font, Color::from_rgba(224, 224, 224, 255),
 				x as f32, y as f32, FontAlign::Left, &format!("AP {}/{}", obj.action_points, obj.max_action_points));
+			y += 10;
+			let text = if obj.level as usize > obj.growth.len()
+			{
+				format!("Lv {}", obj.level)
+			}
+			else
+			{
+				format!("Lv {} XP {}/{}", obj.level, obj.experience, experience_needed(obj))
+			};
+			state.core.draw_text(&state.ui_font, Color::from_rgba(224, 224, 224, 255),
+				x as f32, y as f32, FontAlign::Left, &text);
+			y += 10;
+		}
+		
+		for status in &obj.statuses
+		{
+			state.core.draw_text(&state.ui_font, status.kind.color(),
+				x as f32, y as f32, FontAlign::Left, &format!("{} ({})", status.kind.name(), status.turns));
+			y += 10;
 		}
 		
 		if obj.is_magic_circle
@@ -170,18 +268,20 @@ complex_behavior!
 		if let Some(ref map_data) = map_data
 		{
 			let mut map_data = map_data.borrow_mut();
-			for e in map_data.collision_map.iter_mut()
-			{
-				*e = false;
-			}
+			let mut collision_map = vec![None; map_data.collision_map.len()];
 			for obj in objects.elems_mut()
 			{
 				if self.check_object(obj)
 				{
 					let idx = xy_to_index(obj.tile_x, obj.tile_y, map_data.width, map_data.height);
-					map_data.collision_map[idx] = true;
+					collision_map[idx] = Some(obj.is_ours);
 				}
 			}
+			if collision_map != map_data.collision_map
+			{
+				map_data.collision_map = collision_map;
+				map_data.generation = map_data.generation.wrapping_add(1);
+			}
 		}
 	}
 }
@@ -192,7 +292,8 @@ simple_behavior!
 	{
 		if obj.selected
 		{
-			state.bob_selected.as_ref().unwrap().draw(obj.x - 16.0, obj.y - 16.0, state);
+			let (x, y) = draw_pos(obj, state);
+			state.bob_selected.as_ref().unwrap().draw(x - 16.0, y - 16.0, state);
 		}
 	}
 }
@@ -13,11 +13,6 @@ pub fn populate_from_file<'l, T>(filename: &'l str, val: &mut T) -> Result<(), V
 	val.from_element(&elem, None)
 }
 
-pub fn l1_dist(x1: i32, y1: i32, x2: i32, y2: i32) -> i32
-{
-	(x2 - x1).abs() + (y2 - y1).abs()
-}
-
 pub fn dist(x1: i32, y1: i32, x2: i32, y2: i32) -> i32
 {
 	let dx = (x1 - x2) as f32;